@@ -1,108 +1,859 @@
 pub mod cache {
-    use std::collections::VecDeque;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+    use std::fmt;
+
+    /// How writes issued by processes reach the disk.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[allow(clippy::enum_variant_names)]
+    pub enum WritePolicy {
+        WriteThrough, // Update the cache and write to the disk right away
+        WriteBack,    // Complete into the cache, write dirty buffers back later
+        WriteAround,  // Write to the disk only, dropping any cached copy
+    }
 
     #[derive(Debug)]
     pub struct Buffer {
         pub sector: u32,
         pub counter: u32,
+        pub dirty: bool,     // Holds data not yet written to the disk
+        pub dirtied_at: u32, // Time the buffer became dirty
+    }
+
+    impl Buffer {
+        pub fn new(sector: u32) -> Self {
+            Self {
+                sector,
+                counter: 1,
+                dirty: false,
+                dirtied_at: 0,
+            }
+        }
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CacheOutcome {
+        Hit { segment: &'static str }, // Part of the policy that held the buffer
+        Miss,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct CacheStats {
+        pub hits: BTreeMap<&'static str, u32>, // Hits per segment
+        pub misses: u32,
+        pub promotions: u32, // Buffers moved to a segment they are less likely to be evicted from
+        pub demotions: u32,  // Buffers pushed towards eviction
+        pub evictions: u32,
+        pub agings: u32,                         // Times the counters were halved
+        pub victim_counters: BTreeMap<u32, u32>, // Counter of evicted buffers -> number of victims
+        pub occupancy: Vec<(u32, Vec<(&'static str, usize)>)>, // Time -> buffers per segment
+    }
+
+    impl CacheStats {
+        /// Records a hit in the segment and returns it as the access outcome.
+        pub fn hit(&mut self, segment: &'static str) -> CacheOutcome {
+            *self.hits.entry(segment).or_default() += 1;
+            CacheOutcome::Hit { segment }
+        }
+
+        pub fn miss(&mut self) -> CacheOutcome {
+            self.misses += 1;
+            CacheOutcome::Miss
+        }
+
+        pub fn record_eviction(&mut self, victim: &Buffer) {
+            self.evictions += 1;
+            *self.victim_counters.entry(victim.counter).or_default() += 1;
+        }
+
+        /// Promotions and demotions are only reported for policies with several segments.
+        pub fn print_report(&self, segmented: bool) {
+            let hits: u32 = self.hits.values().sum();
+            let accesses = hits + self.misses;
+            let hit_ratio = if accesses == 0 {
+                0.0
+            } else {
+                100.0 * hits as f64 / accesses as f64
+            };
+            println!(
+                "[REPORT] Cache: {} hits, {} misses ({:.1}% hit ratio)",
+                hits, self.misses, hit_ratio
+            );
+            for (segment, count) in &self.hits {
+                println!("[REPORT]   {} hits in {} segment", count, segment);
+            }
+            if segmented {
+                println!(
+                    "[REPORT] Cache: {} promotions, {} demotions",
+                    self.promotions, self.demotions
+                );
+            }
+            println!(
+                "[REPORT] Cache: {} evictions, {} agings",
+                self.evictions, self.agings
+            );
+            for (counter, victims) in &self.victim_counters {
+                println!(
+                    "[REPORT]   {} victims evicted with counter {}",
+                    victims, counter
+                );
+            }
+
+            // Average and peak buffers per segment over all samples
+            let mut segments: BTreeMap<&'static str, (usize, usize)> = BTreeMap::new();
+            for (_, sizes) in &self.occupancy {
+                for &(segment, len) in sizes {
+                    let (total, peak) = segments.entry(segment).or_default();
+                    *total += len;
+                    *peak = (*peak).max(len);
+                }
+            }
+            for (segment, (total, peak)) in segments {
+                println!(
+                    "[REPORT]   {} segment occupancy: avg {:.1}, peak {} buffers",
+                    segment,
+                    total as f64 / self.occupancy.len() as f64,
+                    peak
+                );
+            }
+        }
+    }
+
+    /// Replacement policy of the buffer cache.
+    pub trait CachePolicy {
+        fn name(&self) -> &'static str;
+        /// Finds the buffer for the sector without updating the replacement state.
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer>;
+        /// Looks the sector up and, on a hit, records the access in the replacement state.
+        fn access(&mut self, sector: u32) -> CacheOutcome;
+        /// Adds a buffer for a sector that is not cached. Returns the buffer evicted to make room.
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer>;
+        /// Removes the buffer the policy would replace next.
+        fn evict(&mut self) -> Option<Buffer>;
+        fn contains(&self, sector: u32) -> bool;
+        /// Drops the buffer for the sector, wherever the policy keeps it.
+        fn remove(&mut self, sector: u32) -> Option<Buffer>;
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_>;
+        /// Number of buffers in each segment of the policy.
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)>;
+        fn stats(&self) -> &CacheStats;
+        fn stats_mut(&mut self) -> &mut CacheStats;
+
+        /// Lets time-based policies know the simulated time before an access.
+        fn advance_time(&mut self, _current_time: u32) {}
+
+        fn record_occupancy(&mut self, current_time: u32) {
+            let sizes = self.segment_sizes();
+            self.stats_mut().occupancy.push((current_time, sizes));
+        }
+
+        /// Caches a sector read from the disk, unless it got cached meanwhile.
+        /// Returns the buffer evicted to make room.
+        fn fill(&mut self, sector: u32) -> Option<Buffer> {
+            if self.contains(sector) {
+                return None;
+            }
+            self.insert(Buffer::new(sector))
+        }
+
+        /// Completes a write into the cache after `access`: the buffer is marked dirty,
+        /// a write miss inserts a new one. Returns the buffer evicted to make room.
+        fn write(&mut self, sector: u32, current_time: u32) -> Option<Buffer> {
+            let evicted = self.fill(sector);
+            if let Some(buffer) = self.lookup(sector) {
+                if !buffer.dirty {
+                    buffer.dirty = true;
+                    buffer.dirtied_at = current_time;
+                }
+            }
+            evicted
+        }
+
+        /// Time the oldest dirty buffer was dirtied, if any.
+        fn oldest_dirty(&mut self) -> Option<u32> {
+            self.buffers_mut()
+                .filter(|buffer| buffer.dirty)
+                .map(|buffer| buffer.dirtied_at)
+                .min()
+        }
+
+        /// Cleans the dirty buffers older than `max_age` and returns their sectors for writing.
+        fn flush_expired(&mut self, current_time: u32, max_age: u32) -> Vec<u32> {
+            let mut sectors = Vec::new();
+            for buffer in self.buffers_mut() {
+                if buffer.dirty && current_time - buffer.dirtied_at >= max_age {
+                    buffer.dirty = false;
+                    sectors.push(buffer.sector);
+                }
+            }
+            sectors.sort();
+            sectors
+        }
+    }
+
+    /// Keeps counters of buffers that were hot long ago from pinning them in the cache.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Aging {
+        None,
+        Halve { interval: u32 }, // Halve all counters every `interval` accesses
+        Decay { half_life: u32 }, // Halve all counters every `half_life` ms of simulated time
+        NoLeftHits,              // Hits in the left segment do not bump the counter
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Segment {
+        Left,   // Newest buffers, hits here do not move them
+        Middle, // Buffers pushed out of the left segment
+        Right,  // Eviction candidates, the lowest counter goes first
+    }
+
+    impl Segment {
+        pub fn name(self) -> &'static str {
+            match self {
+                Segment::Left => "left",
+                Segment::Middle => "middle",
+                Segment::Right => "right",
+            }
+        }
+    }
+
+    struct Node {
+        buffer: Buffer,
+        segment: Segment,
+        prev: Option<usize>, // Towards the front of the segment
+        next: Option<usize>, // Towards the back of the segment
+    }
+
+    #[derive(Default)]
+    struct SegmentList {
+        head: Option<usize>,
+        tail: Option<usize>,
+        len: usize,
+    }
+
+    /// Three-segment LFU cache. Buffers live in a slab and are linked into per-segment lists,
+    /// `index` maps a sector to its slot and `right_buckets` keeps the right segment ordered
+    /// by counter, so lookups, moves and evictions do not scan the segments.
     pub struct LfuCache {
-        pub left: VecDeque<Buffer>,
-        pub middle: VecDeque<Buffer>,
-        pub right: VecDeque<Buffer>,
+        nodes: Vec<Option<Node>>,
+        free: Vec<usize>,
+        index: HashMap<u32, usize>,
+        left: SegmentList,
+        middle: SegmentList,
+        right: SegmentList,
+        right_buckets: BTreeMap<u32, BTreeSet<u32>>, // Counter -> sectors in the right segment
+        pub stats: CacheStats,
         pub left_max: usize,
         pub middle_max: usize,
         pub total_buffers: usize,
+        pub aging: Aging,
+        accesses: u32,
+        last_decay: u32,
     }
 
     impl LfuCache {
-        pub fn new(total_buffers: usize, left_max: usize, middle_max: usize) -> Self {
+        pub fn new(total_buffers: usize, left_max: usize, middle_max: usize, aging: Aging) -> Self {
             Self {
-                left: VecDeque::new(),
-                middle: VecDeque::new(),
-                right: VecDeque::new(),
+                nodes: Vec::new(),
+                free: Vec::new(),
+                index: HashMap::new(),
+                left: SegmentList::default(),
+                middle: SegmentList::default(),
+                right: SegmentList::default(),
+                right_buckets: BTreeMap::new(),
+                stats: CacheStats::default(),
                 left_max,
                 middle_max,
                 total_buffers,
+                aging,
+                accesses: 0,
+                last_decay: 0,
             }
         }
 
-        pub fn access(&mut self, sector: u32) {
-            if let Some(buffer) = self.left.iter_mut().find(|b| b.sector == sector) {
-                println!(
-                    "[LFU] Buffer for sector {} found in left segment. Counter: {}",
-                    sector, buffer.counter
-                );
-                buffer.counter += 1;
-                return;
+        pub fn builder(total_buffers: usize) -> LfuCacheBuilder {
+            LfuCacheBuilder {
+                total_buffers,
+                left: SegmentSize::Buffers(1),
+                middle: SegmentSize::Buffers(0),
+                aging: Aging::None,
             }
+        }
 
-            if let Some(pos) = self.middle.iter().position(|b| b.sector == sector) {
-                let mut buffer = self.middle.remove(pos).unwrap();
-                println!(
-                    "[LFU] Buffer for sector {} moved from middle to left. Counter: {}",
-                    sector, buffer.counter
-                );
+        fn right_max(&self) -> usize {
+            self.total_buffers
+                .saturating_sub(self.left_max + self.middle_max)
+        }
+
+        /// Halves every counter, rounding up so no buffer drops to zero.
+        fn halve_counters(&mut self) {
+            println!("[LFU] Aging: halving buffer counters.");
+            self.stats.agings += 1;
+            for node in self.nodes.iter_mut().flatten() {
+                node.buffer.counter = node.buffer.counter.div_ceil(2);
+            }
+
+            self.right_buckets.clear();
+            let mut slot = self.right.head;
+            while let Some(current) = slot {
+                let node = self.node(current);
+                let (sector, counter, next) = (node.buffer.sector, node.buffer.counter, node.next);
+                self.right_buckets
+                    .entry(counter)
+                    .or_default()
+                    .insert(sector);
+                slot = next;
+            }
+        }
+
+        fn node(&self, slot: usize) -> &Node {
+            self.nodes[slot].as_ref().unwrap()
+        }
+
+        fn node_mut(&mut self, slot: usize) -> &mut Node {
+            self.nodes[slot].as_mut().unwrap()
+        }
+
+        fn list_mut(&mut self, segment: Segment) -> &mut SegmentList {
+            match segment {
+                Segment::Left => &mut self.left,
+                Segment::Middle => &mut self.middle,
+                Segment::Right => &mut self.right,
+            }
+        }
+
+        fn alloc(&mut self, buffer: Buffer) -> usize {
+            let sector = buffer.sector;
+            let node = Node {
+                buffer,
+                segment: Segment::Left,
+                prev: None,
+                next: None,
+            };
+            let slot = if let Some(slot) = self.free.pop() {
+                self.nodes[slot] = Some(node);
+                slot
+            } else {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            };
+            self.index.insert(sector, slot);
+            slot
+        }
+
+        /// Frees an unlinked slot and hands its buffer back.
+        fn release(&mut self, slot: usize) -> Buffer {
+            let node = self.nodes[slot].take().unwrap();
+            self.index.remove(&node.buffer.sector);
+            self.free.push(slot);
+            node.buffer
+        }
+
+        fn push_front(&mut self, segment: Segment, slot: usize) {
+            let old_head = self.list_mut(segment).head;
+            let node = self.node_mut(slot);
+            node.segment = segment;
+            node.prev = None;
+            node.next = old_head;
+            let (sector, counter) = (node.buffer.sector, node.buffer.counter);
+            if let Some(head) = old_head {
+                self.node_mut(head).prev = Some(slot);
+            }
+
+            let list = self.list_mut(segment);
+            list.head = Some(slot);
+            if list.tail.is_none() {
+                list.tail = Some(slot);
+            }
+            list.len += 1;
+
+            if segment == Segment::Right {
+                self.right_buckets
+                    .entry(counter)
+                    .or_default()
+                    .insert(sector);
+            }
+        }
+
+        fn unlink(&mut self, slot: usize) {
+            let node = self.node(slot);
+            let (segment, prev, next) = (node.segment, node.prev, node.next);
+            let (sector, counter) = (node.buffer.sector, node.buffer.counter);
+            match prev {
+                Some(prev) => self.node_mut(prev).next = next,
+                None => self.list_mut(segment).head = next,
+            }
+            match next {
+                Some(next) => self.node_mut(next).prev = prev,
+                None => self.list_mut(segment).tail = prev,
+            }
+            self.list_mut(segment).len -= 1;
+
+            if segment == Segment::Right {
+                if let Some(bucket) = self.right_buckets.get_mut(&counter) {
+                    bucket.remove(&sector);
+                    if bucket.is_empty() {
+                        self.right_buckets.remove(&counter);
+                    }
+                }
+            }
+        }
+
+        fn pop_back(&mut self, segment: Segment) -> Option<usize> {
+            let tail = self.list_mut(segment).tail?;
+            self.unlink(tail);
+            Some(tail)
+        }
+
+        fn move_to_left(&mut self, slot: usize) -> Option<Buffer> {
+            self.push_front(Segment::Left, slot);
+            if self.left.len > self.left_max {
+                let slot = self.pop_back(Segment::Left).unwrap();
+                self.stats.demotions += 1;
+                return self.move_to_middle(slot);
+            }
+            None
+        }
+
+        fn move_to_middle(&mut self, slot: usize) -> Option<Buffer> {
+            self.push_front(Segment::Middle, slot);
+            if self.middle.len > self.middle_max {
+                let slot = self.pop_back(Segment::Middle).unwrap();
+                self.stats.demotions += 1;
+                return self.move_to_right(slot);
+            }
+            None
+        }
+
+        fn move_to_right(&mut self, slot: usize) -> Option<Buffer> {
+            self.push_front(Segment::Right, slot);
+            if self.right.len > self.right_max() {
+                return self.evict();
+            }
+            None
+        }
+
+        fn add_to_left(&mut self, buffer: Buffer) -> Option<Buffer> {
+            let slot = self.alloc(buffer);
+            self.move_to_left(slot)
+        }
+    }
+
+    impl CachePolicy for LfuCache {
+        fn name(&self) -> &'static str {
+            "LFU"
+        }
+
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer> {
+            let slot = *self.index.get(&sector)?;
+            Some(&mut self.node_mut(slot).buffer)
+        }
+
+        fn access(&mut self, sector: u32) -> CacheOutcome {
+            self.accesses += 1;
+            if let Aging::Halve { interval } = self.aging {
+                if self.accesses.is_multiple_of(interval) {
+                    self.halve_counters();
+                }
+            }
+
+            let Some(&slot) = self.index.get(&sector) else {
+                return self.stats.miss();
+            };
+            let segment = self.node(slot).segment;
+            if segment != Segment::Left {
+                self.unlink(slot);
+            }
+            let count_hit = !(segment == Segment::Left && self.aging == Aging::NoLeftHits);
+            let buffer = &mut self.node_mut(slot).buffer;
+            let counter = buffer.counter;
+            if count_hit {
                 buffer.counter += 1;
-                self.move_to_left(buffer);
-                return;
             }
 
-            if let Some(pos) = self.right.iter().position(|b| b.sector == sector) {
-                let mut buffer = self.right.remove(pos).unwrap();
-                println!(
+            match segment {
+                Segment::Left => println!(
+                    "[LFU] Buffer for sector {} found in left segment. Counter: {}",
+                    sector, counter
+                ),
+                Segment::Middle => println!(
+                    "[LFU] Buffer for sector {} moved from middle to left. Counter: {}",
+                    sector, counter
+                ),
+                Segment::Right => println!(
                     "[LFU] Buffer for sector {} moved from right to left. Counter: {}",
-                    sector, buffer.counter
-                );
-                buffer.counter += 1;
-                self.move_to_left(buffer);
-                return;
+                    sector, counter
+                ),
+            }
+            if segment != Segment::Left {
+                self.stats.promotions += 1;
+                self.move_to_left(slot);
             }
+            self.stats.hit(segment.name())
+        }
 
+        fn advance_time(&mut self, current_time: u32) {
+            if let Aging::Decay { half_life } = self.aging {
+                while half_life > 0 && current_time - self.last_decay >= half_life {
+                    self.last_decay += half_life;
+                    self.halve_counters();
+                }
+            }
+        }
+
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer> {
             println!(
                 "[LFU] Buffer for sector {} not found. Adding to left.",
-                sector
+                buffer.sector
             );
-            self.add_to_left(Buffer { sector, counter: 1 });
+            self.add_to_left(buffer)
         }
 
-        fn move_to_left(&mut self, buffer: Buffer) {
-            self.left.push_front(buffer);
-            if self.left.len() > self.left_max {
-                let buffer = self.left.pop_back().unwrap();
-                self.move_to_middle(buffer);
+        fn evict(&mut self) -> Option<Buffer> {
+            let victim = self
+                .right_buckets
+                .iter()
+                .next()
+                .and_then(|(&counter, sectors)| Some((counter, *sectors.iter().next()?)));
+            if let Some((counter, sector)) = victim {
+                println!(
+                    "[LFU] Buffer for sector {} evicted from cache due to minimum counter {}.",
+                    sector, counter
+                );
+                let slot = self.index[&sector];
+                self.unlink(slot);
+                let buffer = self.release(slot);
+                self.stats.record_eviction(&buffer);
+                return Some(buffer);
             }
+            // Without a right segment the oldest buffer of the other segments goes
+            let slot = self
+                .pop_back(Segment::Middle)
+                .or_else(|| self.pop_back(Segment::Left))?;
+            let buffer = self.release(slot);
+            self.stats.record_eviction(&buffer);
+            Some(buffer)
+        }
+
+        fn contains(&self, sector: u32) -> bool {
+            self.index.contains_key(&sector)
         }
 
-        fn move_to_middle(&mut self, buffer: Buffer) {
-            self.middle.push_front(buffer);
-            if self.middle.len() > self.middle_max {
-                let buffer = self.middle.pop_back().unwrap();
-                self.move_to_right(buffer);
+        fn remove(&mut self, sector: u32) -> Option<Buffer> {
+            let slot = *self.index.get(&sector)?;
+            println!("[LFU] Buffer for sector {} invalidated.", sector);
+            self.unlink(slot);
+            Some(self.release(slot))
+        }
+
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_> {
+            Box::new(self.nodes.iter_mut().flatten().map(|node| &mut node.buffer))
+        }
+
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)> {
+            vec![
+                (Segment::Left.name(), self.left.len),
+                (Segment::Middle.name(), self.middle.len),
+                (Segment::Right.name(), self.right.len),
+            ]
+        }
+
+        fn stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn stats_mut(&mut self) -> &mut CacheStats {
+            &mut self.stats
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum CacheConfigError {
+        ZeroCapacity,
+        EmptyLeftSegment, // New buffers enter the left segment, it needs room for one
+        SegmentsExceedTotal {
+            left_max: usize,
+            middle_max: usize,
+            total_buffers: usize,
+        },
+        InvalidPercentage {
+            segment: Segment,
+            percent: u32,
+        },
+    }
+
+    impl fmt::Display for CacheConfigError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                CacheConfigError::ZeroCapacity => write!(f, "cache must hold at least one buffer"),
+                CacheConfigError::EmptyLeftSegment => {
+                    write!(f, "left segment must hold at least one buffer")
+                }
+                CacheConfigError::SegmentsExceedTotal {
+                    left_max,
+                    middle_max,
+                    total_buffers,
+                } => write!(
+                    f,
+                    "left ({}) and middle ({}) segments exceed the {} buffers of the cache",
+                    left_max, middle_max, total_buffers
+                ),
+                CacheConfigError::InvalidPercentage { segment, percent } => write!(
+                    f,
+                    "{}% for the {} segment is out of range",
+                    percent,
+                    segment.name()
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for CacheConfigError {}
+
+    #[derive(Debug, Clone, Copy)]
+    enum SegmentSize {
+        Buffers(usize),
+        Percent(u32), // Share of the total buffers
+    }
+
+    /// Validated construction of an `LfuCache`. The right segment gets the buffers left
+    /// over after the left and middle segments.
+    pub struct LfuCacheBuilder {
+        total_buffers: usize,
+        left: SegmentSize,
+        middle: SegmentSize,
+        aging: Aging,
+    }
+
+    impl LfuCacheBuilder {
+        pub fn left(mut self, buffers: usize) -> Self {
+            self.left = SegmentSize::Buffers(buffers);
+            self
+        }
+
+        pub fn middle(mut self, buffers: usize) -> Self {
+            self.middle = SegmentSize::Buffers(buffers);
+            self
+        }
+
+        pub fn left_percent(mut self, percent: u32) -> Self {
+            self.left = SegmentSize::Percent(percent);
+            self
+        }
+
+        pub fn middle_percent(mut self, percent: u32) -> Self {
+            self.middle = SegmentSize::Percent(percent);
+            self
+        }
+
+        pub fn aging(mut self, aging: Aging) -> Self {
+            self.aging = aging;
+            self
+        }
+
+        fn resolve(&self, size: SegmentSize, segment: Segment) -> Result<usize, CacheConfigError> {
+            match size {
+                SegmentSize::Buffers(buffers) => Ok(buffers),
+                SegmentSize::Percent(percent) if percent > 100 => {
+                    Err(CacheConfigError::InvalidPercentage { segment, percent })
+                }
+                SegmentSize::Percent(percent) => Ok(self.total_buffers * percent as usize / 100),
+            }
+        }
+
+        pub fn build(self) -> Result<LfuCache, CacheConfigError> {
+            if self.total_buffers == 0 {
+                return Err(CacheConfigError::ZeroCapacity);
+            }
+            let left_max = self.resolve(self.left, Segment::Left)?;
+            let middle_max = self.resolve(self.middle, Segment::Middle)?;
+            if left_max == 0 {
+                return Err(CacheConfigError::EmptyLeftSegment);
+            }
+            if left_max + middle_max > self.total_buffers {
+                return Err(CacheConfigError::SegmentsExceedTotal {
+                    left_max,
+                    middle_max,
+                    total_buffers: self.total_buffers,
+                });
             }
+            Ok(LfuCache::new(
+                self.total_buffers,
+                left_max,
+                middle_max,
+                self.aging,
+            ))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::golden::golden::assert_golden;
+        use proptest::prelude::*;
+        use std::collections::HashSet;
+        use std::fmt::Write as _;
+
+        /// Sectors of a segment from front to back, with their counters.
+        fn segment(cache: &LfuCache, list: &SegmentList) -> String {
+            let mut entries = Vec::new();
+            let mut slot = list.head;
+            while let Some(current) = slot {
+                let node = cache.node(current);
+                entries.push(format!("{}:{}", node.buffer.sector, node.buffer.counter));
+                slot = node.next;
+            }
+            entries.join(" ")
+        }
+
+        /// Accesses every sector the way the system does, filling it on a miss, and
+        /// logs the outcome, the evicted buffer and the segments after each step.
+        fn run(mut cache: LfuCache, sectors: &[u32]) -> String {
+            let mut output = String::new();
+            for &sector in sectors {
+                let outcome = match cache.access(sector) {
+                    CacheOutcome::Hit { segment } => format!("hit {}", segment),
+                    CacheOutcome::Miss => "miss".to_string(),
+                };
+                let evicted = match cache.fill(sector) {
+                    Some(buffer) => format!(", evicted {}", buffer.sector),
+                    None => String::new(),
+                };
+                let _ = writeln!(
+                    output,
+                    "{:>3}: {}{} | left [{}] middle [{}] right [{}]",
+                    sector,
+                    outcome,
+                    evicted,
+                    segment(&cache, &cache.left),
+                    segment(&cache, &cache.middle),
+                    segment(&cache, &cache.right)
+                );
+            }
+            let stats = &cache.stats;
+            let _ = writeln!(
+                output,
+                "promotions {}, demotions {}, evictions {}",
+                stats.promotions, stats.demotions, stats.evictions
+            );
+            output
+        }
+
+        #[test]
+        fn lfu_promotion_and_eviction() {
+            let cache = LfuCache::new(5, 2, 1, Aging::None);
+            let sectors = [1, 2, 3, 1, 4, 5, 6, 3, 3, 7, 2, 8, 1, 9, 6];
+            assert_golden("lfu", &run(cache, &sectors));
+        }
+
+        #[test]
+        fn lfu_halving_and_left_hits() {
+            let halve = LfuCache::new(4, 1, 1, Aging::Halve { interval: 4 });
+            let sectors = [1, 1, 1, 2, 3, 1, 4, 5, 2, 2, 6, 1];
+            assert_golden("lfu_halve", &run(halve, &sectors));
+
+            let fbr = LfuCache::new(4, 2, 1, Aging::NoLeftHits);
+            let sectors = [1, 1, 1, 2, 3, 1, 4, 5, 1, 6, 7];
+            assert_golden("lfu_fbr", &run(fbr, &sectors));
         }
 
-        fn move_to_right(&mut self, buffer: Buffer) {
-            self.right.push_front(buffer);
-            if self.right.len() > self.total_buffers - self.left_max - self.middle_max {
-                if let Some((min_index, _)) = self
-                    .right
-                    .iter()
-                    .enumerate()
-                    .min_by_key(|&(_, buf)| (buf.counter, buf.sector))
-                {
-                    println!(
-                        "[LFU] Buffer for sector {} evicted from cache due to minimum counter {}.",
-                        self.right[min_index].sector, self.right[min_index].counter
+        #[derive(Debug, Clone)]
+        enum Step {
+            Read(u32),
+            Write(u32),
+            Remove(u32), // Write-around invalidation
+        }
+
+        fn steps() -> impl Strategy<Value = Vec<Step>> {
+            prop::collection::vec(
+                prop_oneof![
+                    3 => (0..24u32).prop_map(Step::Read),
+                    2 => (0..24u32).prop_map(Step::Write),
+                    1 => (0..24u32).prop_map(Step::Remove),
+                ],
+                0..120,
+            )
+        }
+
+        /// Total, left and middle buffers of any valid segment split of up to 12
+        /// buffers, with any aging.
+        fn lfu_config() -> impl Strategy<Value = (usize, usize, usize, Aging)> {
+            let aging = prop_oneof![
+                Just(Aging::None),
+                (1..8u32).prop_map(|interval| Aging::Halve { interval }),
+                (1..8u32).prop_map(|half_life| Aging::Decay { half_life }),
+                Just(Aging::NoLeftHits),
+            ];
+            (1..=12usize)
+                .prop_flat_map(|total| (Just(total), 1..=total))
+                .prop_flat_map(move |(total, left)| {
+                    (Just(total), Just(left), 0..=total - left, aging.clone())
+                })
+        }
+
+        /// No segment is over its limit and every cached sector is linked into exactly
+        /// one segment, once.
+        fn check_segments(cache: &LfuCache) -> Result<(), TestCaseError> {
+            let mut seen = HashSet::new();
+            let segments = [
+                (&cache.left, cache.left_max),
+                (&cache.middle, cache.middle_max),
+                (&cache.right, cache.right_max()),
+            ];
+            for (list, max) in segments {
+                prop_assert!(
+                    list.len <= max,
+                    "{} buffers in a segment of {}",
+                    list.len,
+                    max
+                );
+                let mut len = 0;
+                let mut slot = list.head;
+                while let Some(current) = slot {
+                    let node = cache.node(current);
+                    prop_assert!(
+                        seen.insert(node.buffer.sector),
+                        "sector {} cached twice",
+                        node.buffer.sector
                     );
-                    self.right.remove(min_index);
+                    len += 1;
+                    slot = node.next;
                 }
+                prop_assert_eq!(len, list.len);
             }
+            prop_assert_eq!(seen.len(), cache.index.len());
+            prop_assert!(seen.len() <= cache.total_buffers);
+            Ok(())
         }
 
-        fn add_to_left(&mut self, buffer: Buffer) {
-            self.move_to_left(buffer);
+        proptest! {
+            #[test]
+            fn lfu_segments_stay_within_limits(config in lfu_config(), steps in steps()) {
+                let (total, left, middle, aging) = config;
+                let mut cache = LfuCache::builder(total)
+                    .left(left)
+                    .middle(middle)
+                    .aging(aging)
+                    .build()
+                    .unwrap();
+                for (time, step) in steps.into_iter().enumerate() {
+                    cache.advance_time(time as u32);
+                    match step {
+                        Step::Read(sector) => {
+                            if cache.access(sector) == CacheOutcome::Miss {
+                                cache.fill(sector);
+                            }
+                        }
+                        Step::Write(sector) => {
+                            cache.access(sector);
+                            cache.write(sector, time as u32);
+                        }
+                        Step::Remove(sector) => {
+                            cache.remove(sector);
+                        }
+                    }
+                    check_segments(&cache)?;
+                }
+            }
         }
     }
 }
@@ -0,0 +1,358 @@
+pub mod cpu {
+    use crate::process::process::Process;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+    /// Decides which ready process gets the CPU next and for how long.
+    pub trait CpuScheduler {
+        fn name(&self) -> &'static str;
+        /// The process became ready.
+        fn enqueue(&mut self, process: &Process);
+        /// The process left the ready set without being picked.
+        fn remove(&mut self, process_id: u32);
+        fn pick_next(&mut self) -> Option<u32>;
+        /// CPU time the process may use before it is preempted.
+        fn time_slice(&self, process: &Process) -> u32;
+        /// The process left the CPU after using `used` ms of its slice.
+        fn account(&mut self, process: &Process, used: u32, slice_expired: bool);
+        fn print_queue_status(&self);
+    }
+
+    /// Processes run in arrival order, each for its own quantum.
+    pub struct RoundRobin {
+        pub ready: VecDeque<u32>,
+    }
+
+    impl RoundRobin {
+        pub fn new() -> Self {
+            Self {
+                ready: VecDeque::new(),
+            }
+        }
+    }
+
+    impl CpuScheduler for RoundRobin {
+        fn name(&self) -> &'static str {
+            "round-robin"
+        }
+
+        fn enqueue(&mut self, process: &Process) {
+            self.ready.push_back(process.id);
+        }
+
+        fn remove(&mut self, process_id: u32) {
+            self.ready.retain(|&id| id != process_id);
+        }
+
+        fn pick_next(&mut self) -> Option<u32> {
+            self.ready.pop_front()
+        }
+
+        fn time_slice(&self, process: &Process) -> u32 {
+            process.quantum
+        }
+
+        fn account(&mut self, _process: &Process, _used: u32, _slice_expired: bool) {}
+
+        fn print_queue_status(&self) {
+            println!("[RR] Ready: {:?}", self.ready);
+        }
+    }
+
+    /// Static priorities from the nice values, lowest nice first. Round-robin among
+    /// equal nice values. There is no aging: as long as a process with a lower nice
+    /// value is ready, the others starve. Use MLFQ or CFS for a fair share.
+    pub struct PriorityScheduler {
+        pub ready: BTreeMap<i32, VecDeque<u32>>, // Nice value -> processes in arrival order
+    }
+
+    impl PriorityScheduler {
+        pub fn new() -> Self {
+            Self {
+                ready: BTreeMap::new(),
+            }
+        }
+    }
+
+    impl CpuScheduler for PriorityScheduler {
+        fn name(&self) -> &'static str {
+            "priority"
+        }
+
+        fn enqueue(&mut self, process: &Process) {
+            self.ready
+                .entry(process.nice)
+                .or_default()
+                .push_back(process.id);
+        }
+
+        fn remove(&mut self, process_id: u32) {
+            for queue in self.ready.values_mut() {
+                queue.retain(|&id| id != process_id);
+            }
+            self.ready.retain(|_, queue| !queue.is_empty());
+        }
+
+        fn pick_next(&mut self) -> Option<u32> {
+            let mut entry = self.ready.first_entry()?;
+            let process_id = entry.get_mut().pop_front();
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+            process_id
+        }
+
+        fn time_slice(&self, process: &Process) -> u32 {
+            process.quantum
+        }
+
+        fn account(&mut self, _process: &Process, _used: u32, _slice_expired: bool) {}
+
+        fn print_queue_status(&self) {
+            println!("[PRIORITY] Ready: {:?}", self.ready);
+        }
+    }
+
+    /// Multilevel feedback queue. Processes start at level 0, using up a whole slice
+    /// moves them one level down where slices are twice as long. Every `boost_interval`
+    /// ms of CPU time all processes go back to level 0, so CPU hogs cannot starve.
+    pub struct Mlfq {
+        pub levels: Vec<VecDeque<u32>>,
+        pub level_of: HashMap<u32, usize>,
+        pub base_quantum: u32,
+        pub boost_interval: u32,
+        pub since_boost: u32,
+    }
+
+    impl Mlfq {
+        pub fn new(levels: usize, base_quantum: u32, boost_interval: u32) -> Self {
+            Self {
+                levels: vec![VecDeque::new(); levels.max(1)],
+                level_of: HashMap::new(),
+                base_quantum: base_quantum.max(1),
+                boost_interval,
+                since_boost: 0,
+            }
+        }
+
+        fn level(&self, process_id: u32) -> usize {
+            self.level_of.get(&process_id).copied().unwrap_or(0)
+        }
+
+        fn boost(&mut self) {
+            println!("[MLFQ] Priority boost, all processes back to level 0.");
+            self.level_of.clear();
+            let (top, lower) = self.levels.split_at_mut(1);
+            for queue in lower {
+                top[0].extend(queue.drain(..));
+            }
+            self.since_boost = 0;
+        }
+    }
+
+    impl CpuScheduler for Mlfq {
+        fn name(&self) -> &'static str {
+            "mlfq"
+        }
+
+        fn enqueue(&mut self, process: &Process) {
+            let level = self.level(process.id);
+            self.levels[level].push_back(process.id);
+        }
+
+        fn remove(&mut self, process_id: u32) {
+            for queue in &mut self.levels {
+                queue.retain(|&id| id != process_id);
+            }
+        }
+
+        fn pick_next(&mut self) -> Option<u32> {
+            self.levels.iter_mut().find_map(|queue| queue.pop_front())
+        }
+
+        fn time_slice(&self, process: &Process) -> u32 {
+            // Deep levels saturate instead of overflowing
+            1u32.checked_shl(self.level(process.id) as u32)
+                .map_or(u32::MAX, |factor| self.base_quantum.saturating_mul(factor))
+        }
+
+        fn account(&mut self, process: &Process, used: u32, slice_expired: bool) {
+            let level = self.level(process.id);
+            if slice_expired && level + 1 < self.levels.len() {
+                println!(
+                    "[MLFQ] Process {} used its whole slice, demoted to level {}.",
+                    process.id,
+                    level + 1
+                );
+                self.level_of.insert(process.id, level + 1);
+            }
+            self.since_boost += used;
+            if self.since_boost >= self.boost_interval {
+                self.boost();
+            }
+        }
+
+        fn print_queue_status(&self) {
+            for (level, queue) in self.levels.iter().enumerate() {
+                println!("[MLFQ] Level {}: {:?}", level, queue);
+            }
+        }
+    }
+
+    /// Completely-fair-style scheduler. The ready process with the least virtual
+    /// runtime runs next, for its share of `target_latency`. Virtual runtime grows
+    /// slower for processes with a lower nice value.
+    pub struct Cfs {
+        pub ready: BTreeSet<(u64, u32)>, // (vruntime, process id)
+        pub vruntime: HashMap<u32, u64>,
+        pub target_latency: u32,
+        pub min_granularity: u32,
+    }
+
+    impl Cfs {
+        pub fn new(target_latency: u32, min_granularity: u32) -> Self {
+            Self {
+                ready: BTreeSet::new(),
+                vruntime: HashMap::new(),
+                target_latency,
+                min_granularity: min_granularity.max(1),
+            }
+        }
+
+        /// Load weight of a nice value, 1024 at nice 0 and about 25% less per level.
+        pub fn weight(nice: i32) -> u64 {
+            (1024.0 / 1.25f64.powi(nice)).round().max(1.0) as u64
+        }
+
+        fn min_vruntime(&self) -> u64 {
+            self.ready.first().map_or(0, |&(vruntime, _)| vruntime)
+        }
+    }
+
+    impl CpuScheduler for Cfs {
+        fn name(&self) -> &'static str {
+            "cfs"
+        }
+
+        fn enqueue(&mut self, process: &Process) {
+            // A process that slept does not get to catch up on all the time it missed
+            let min_vruntime = self.min_vruntime();
+            let vruntime = self.vruntime.entry(process.id).or_insert(min_vruntime);
+            *vruntime = (*vruntime).max(min_vruntime);
+            self.ready.insert((*vruntime, process.id));
+        }
+
+        fn remove(&mut self, process_id: u32) {
+            self.ready.retain(|&(_, id)| id != process_id);
+        }
+
+        fn pick_next(&mut self) -> Option<u32> {
+            self.ready.pop_first().map(|(_, process_id)| process_id)
+        }
+
+        fn time_slice(&self, process: &Process) -> u32 {
+            let running = self.ready.len() as u64 + 1;
+            let share = self.target_latency as u64 * Self::weight(process.nice) / 1024 / running;
+            u32::try_from(share)
+                .unwrap_or(u32::MAX)
+                .max(self.min_granularity)
+        }
+
+        fn account(&mut self, process: &Process, used: u32, _slice_expired: bool) {
+            let delta = used as u64 * 1024 / Self::weight(process.nice);
+            *self.vruntime.entry(process.id).or_insert(0) += delta;
+        }
+
+        fn print_queue_status(&self) {
+            println!("[CFS] Ready (vruntime, process): {:?}", self.ready);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn zero_settings_still_give_cpu_time() {
+            let process = Process::new(1, Vec::new(), 0);
+            assert_eq!(RoundRobin::new().time_slice(&process), 1);
+            assert_eq!(PriorityScheduler::new().time_slice(&process), 1);
+            assert_eq!(Mlfq::new(3, 0, 100).time_slice(&process), 1);
+            assert_eq!(Cfs::new(0, 0).time_slice(&process), 1);
+        }
+
+        fn process(id: u32, nice: i32) -> Process {
+            Process::new(id, Vec::new(), 10).with_nice(nice)
+        }
+
+        #[test]
+        fn priority_runs_lowest_nice_first() {
+            let mut scheduler = PriorityScheduler::new();
+            for (id, nice) in [(1, 5), (2, 0), (3, 5), (4, 0)] {
+                scheduler.enqueue(&process(id, nice));
+            }
+            let order: Vec<u32> = std::iter::from_fn(|| scheduler.pick_next()).collect();
+            assert_eq!(order, [2, 4, 1, 3]);
+        }
+
+        #[test]
+        fn mlfq_demotes_after_a_whole_slice() {
+            let mut mlfq = Mlfq::new(3, 10, 1000);
+            let (hog, interactive) = (process(1, 0), process(2, 0));
+            mlfq.account(&hog, 10, true);
+            mlfq.account(&interactive, 4, false);
+            assert_eq!(mlfq.level(hog.id), 1);
+            assert_eq!(mlfq.time_slice(&hog), 20);
+            assert_eq!(mlfq.level(interactive.id), 0);
+
+            mlfq.enqueue(&hog);
+            mlfq.enqueue(&interactive);
+            assert_eq!(mlfq.pick_next(), Some(2));
+            assert_eq!(mlfq.pick_next(), Some(1));
+        }
+
+        #[test]
+        fn mlfq_boost_moves_everyone_back_to_level_0() {
+            let mut mlfq = Mlfq::new(3, 10, 50);
+            let (hog, other) = (process(1, 0), process(2, 0));
+            mlfq.account(&hog, 10, true);
+            mlfq.account(&hog, 20, true);
+            mlfq.enqueue(&hog);
+            assert_eq!(mlfq.levels[2], [1]);
+
+            // 30 + 20 ms of CPU time reach the boost interval
+            mlfq.account(&other, 20, false);
+            assert_eq!(mlfq.since_boost, 0);
+            assert_eq!(mlfq.levels[0], [1]);
+            assert_eq!(mlfq.time_slice(&hog), 10);
+        }
+
+        #[test]
+        fn cfs_picks_lowest_vruntime() {
+            let mut cfs = Cfs::new(40, 5);
+            let (light, heavy, niced) = (process(1, 0), process(2, 0), process(3, 5));
+            cfs.account(&light, 10, false);
+            cfs.account(&heavy, 30, false);
+            // Nice 5 weighs about a third of nice 0, its 11ms count as 33
+            cfs.account(&niced, 11, false);
+            assert_eq!(cfs.vruntime[&3], 33);
+
+            for process in [&light, &heavy, &niced] {
+                cfs.enqueue(process);
+            }
+            let order: Vec<u32> = std::iter::from_fn(|| cfs.pick_next()).collect();
+            assert_eq!(order, [1, 2, 3]);
+        }
+
+        #[test]
+        fn deep_mlfq_levels_saturate() {
+            let mut mlfq = Mlfq::new(40, 10, u32::MAX);
+            let process = Process::new(1, Vec::new(), 10);
+            for level in [31, 32, 39] {
+                mlfq.level_of.insert(process.id, level);
+                assert_eq!(mlfq.time_slice(&process), u32::MAX);
+            }
+            mlfq.level_of.insert(process.id, 3);
+            assert_eq!(mlfq.time_slice(&process), 80);
+        }
+    }
+}
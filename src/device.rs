@@ -0,0 +1,80 @@
+pub mod device {
+    use crate::disk::disk::{PowerModel, TrackBuffer};
+    use crate::scheduler::scheduler::IoOperation;
+    use crate::zoned::zoned::ZoneError;
+    use std::fmt;
+    use std::ops::Range;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum DeviceError {
+        OutOfRange {
+            sector: u32,
+            count: u32,
+            capacity: u32,
+        },
+        NoFreePages {
+            die: usize, // Flash die without an erased page for the write
+        },
+        Zone(ZoneError), // The write broke the rules of a zoned device
+        NoZones,         // Zone reset on a device or volume without zones
+    }
+
+    impl fmt::Display for DeviceError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                DeviceError::OutOfRange {
+                    sector,
+                    count,
+                    capacity,
+                } => write!(
+                    f,
+                    "sectors {}..{} run past the end of the device ({} sectors)",
+                    sector,
+                    *sector as u64 + *count as u64,
+                    capacity
+                ),
+                DeviceError::NoFreePages { die } => {
+                    write!(f, "die {} has no free pages left", die)
+                }
+                DeviceError::Zone(err) => write!(f, "zone error: {}", err),
+                DeviceError::NoZones => write!(f, "device has no zones to reset"),
+            }
+        }
+    }
+
+    impl std::error::Error for DeviceError {}
+
+    impl From<ZoneError> for DeviceError {
+        fn from(err: ZoneError) -> Self {
+            DeviceError::Zone(err)
+        }
+    }
+
+    /// Storage a volume member is built on.
+    pub trait BlockDevice {
+        fn name(&self) -> &'static str;
+        /// Number of addressable sectors.
+        fn capacity(&self) -> u32;
+        /// Serves `count` sectors starting at `sector`, returns the time it took.
+        fn access(
+            &mut self,
+            sector: u32,
+            count: u32,
+            operation: IoOperation,
+        ) -> Result<u32, DeviceError>;
+        /// Resets the zone holding `sector`, dropping its data. Returns the sectors
+        /// of the zone.
+        fn reset_zone(&mut self, _sector: u32) -> Result<Range<u32>, DeviceError> {
+            Err(DeviceError::NoZones)
+        }
+        /// On-drive read cache, if the device has one.
+        fn track_buffer(&self) -> Option<&TrackBuffer> {
+            None
+        }
+        /// Power management, if the device saves power when idle.
+        fn power(&mut self) -> Option<&mut PowerModel> {
+            None
+        }
+        fn print_report(&self);
+    }
+}
@@ -1,11 +1,245 @@
 pub mod disk {
+    use crate::device::device::{BlockDevice, DeviceError};
+    use crate::scheduler::scheduler::IoOperation;
+    use std::collections::VecDeque;
+
+    #[derive(Debug, Default)]
+    pub struct TrackBufferStats {
+        pub hits: u32,        // Reads served entirely from the buffer
+        pub misses: u32,      // Reads that needed at least one track from the media
+        pub tracks_read: u32, // Whole tracks read into the buffer
+        pub evictions: u32,
+        pub invalidations: u32, // Buffered tracks dropped because a write changed them
+    }
+
+    /// On-drive read cache. Each segment holds one whole track, segments are reused
+    /// least recently used first.
+    pub struct TrackBuffer {
+        pub segments: VecDeque<u32>, // Buffered tracks, most recently used at the front
+        pub capacity: usize,
+        pub stats: TrackBufferStats,
+    }
+
+    impl TrackBuffer {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                segments: VecDeque::new(),
+                capacity: capacity.max(1),
+                stats: TrackBufferStats::default(),
+            }
+        }
+
+        pub fn contains(&self, track: u32) -> bool {
+            self.segments.contains(&track)
+        }
+
+        /// Moves a buffered track to the front.
+        pub fn touch(&mut self, track: u32) {
+            if let Some(pos) = self.segments.iter().position(|&t| t == track) {
+                self.segments.remove(pos);
+                self.segments.push_front(track);
+            }
+        }
+
+        pub fn insert(&mut self, track: u32) {
+            if self.contains(track) {
+                self.touch(track);
+                return;
+            }
+            if self.segments.len() >= self.capacity {
+                if let Some(evicted) = self.segments.pop_back() {
+                    println!("[DISK] Track buffer: evicting track {}.", evicted);
+                    self.stats.evictions += 1;
+                }
+            }
+            self.segments.push_front(track);
+            self.stats.tracks_read += 1;
+        }
+
+        /// Drops a buffered track, its copy is stale after a write.
+        pub fn invalidate(&mut self, track: u32) {
+            if let Some(pos) = self.segments.iter().position(|&t| t == track) {
+                self.segments.remove(pos);
+                self.stats.invalidations += 1;
+                println!(
+                    "[DISK] Track buffer: write to track {}, dropping it.",
+                    track
+                );
+            }
+        }
+
+        pub fn print_report(&self) {
+            println!(
+                "[REPORT] Drive track buffer: {} segments, {} hits, {} misses, {} tracks read, {} evictions, {} invalidations",
+                self.capacity,
+                self.stats.hits,
+                self.stats.misses,
+                self.stats.tracks_read,
+                self.stats.evictions,
+                self.stats.invalidations
+            );
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum PowerState {
+        Active,  // Transferring
+        Idle,    // Platters spinning, heads parked
+        Standby, // Spun down
+    }
+
+    #[derive(Debug, Default)]
+    pub struct PowerStats {
+        pub active_time: u32,
+        pub idle_time: u32,
+        pub standby_time: u32,
+        pub spin_ups: u32,
+        pub spin_up_time: u32,
+        pub energy: f64, // Joules
+    }
+
+    /// Spindle power management. After a request the disk idles with the platters
+    /// spinning and spins down to standby once it has been idle for `idle_timeout`
+    /// ms. A request arriving in standby first waits `spin_up_time`. Power draws are
+    /// in watts.
+    pub struct PowerModel {
+        pub idle_timeout: Option<u32>, // Never spins down when absent
+        pub spin_up_time: u32,
+        pub active_watts: f64,
+        pub idle_watts: f64,
+        pub standby_watts: f64,
+        pub spin_up_watts: f64,
+        pub state: PowerState,
+        pub idle_since: u32, // End of the last request
+        pub accounted: u32,  // Time the stats are complete up to
+        pub stats: PowerStats,
+    }
+
+    impl PowerModel {
+        pub fn new(idle_timeout: Option<u32>, spin_up_time: u32) -> Self {
+            Self {
+                idle_timeout,
+                spin_up_time,
+                active_watts: 8.0,
+                idle_watts: 5.0,
+                standby_watts: 1.0,
+                spin_up_watts: 20.0,
+                state: PowerState::Idle,
+                idle_since: 0,
+                accounted: 0,
+                stats: PowerStats::default(),
+            }
+        }
+
+        pub fn with_power_draw(
+            mut self,
+            active_watts: f64,
+            idle_watts: f64,
+            standby_watts: f64,
+            spin_up_watts: f64,
+        ) -> Self {
+            self.active_watts = active_watts;
+            self.idle_watts = idle_watts;
+            self.standby_watts = standby_watts;
+            self.spin_up_watts = spin_up_watts;
+            self
+        }
+
+        fn charge(&mut self, state: PowerState, time: u32) {
+            let watts = match state {
+                PowerState::Active => {
+                    self.stats.active_time += time;
+                    self.active_watts
+                }
+                PowerState::Idle => {
+                    self.stats.idle_time += time;
+                    self.idle_watts
+                }
+                PowerState::Standby => {
+                    self.stats.standby_time += time;
+                    self.standby_watts
+                }
+            };
+            self.stats.energy += watts * time as f64 / 1000.0;
+        }
+
+        /// Accounts the time without requests up to `now`, spinning down when the
+        /// idle timeout passes.
+        pub fn idle_until(&mut self, now: u32) {
+            if now <= self.accounted {
+                return;
+            }
+            if self.state != PowerState::Standby {
+                let spin_down_at = self.idle_timeout.map(|timeout| self.idle_since + timeout);
+                match spin_down_at {
+                    Some(spin_down_at) if spin_down_at <= now => {
+                        self.charge(
+                            PowerState::Idle,
+                            spin_down_at.saturating_sub(self.accounted),
+                        );
+                        println!(
+                            "[DISK] Idle since {}, spinning down at {}.",
+                            self.idle_since, spin_down_at
+                        );
+                        self.state = PowerState::Standby;
+                        self.accounted = self.accounted.max(spin_down_at);
+                    }
+                    _ => self.state = PowerState::Idle,
+                }
+            }
+            self.charge(self.state, now - self.accounted);
+            self.accounted = now;
+        }
+
+        /// Gets the disk ready for a request at `now`. Returns the spin-up latency.
+        pub fn wake(&mut self, now: u32) -> u32 {
+            self.idle_until(now);
+            let latency = if self.state == PowerState::Standby {
+                println!("[DISK] Spinning up for {}ms.", self.spin_up_time);
+                self.stats.spin_ups += 1;
+                self.stats.spin_up_time += self.spin_up_time;
+                self.stats.energy += self.spin_up_watts * self.spin_up_time as f64 / 1000.0;
+                self.spin_up_time
+            } else {
+                0
+            };
+            self.state = PowerState::Active;
+            self.accounted = now + latency;
+            self.idle_since = self.accounted;
+            latency
+        }
+
+        /// The disk transferred for `time` ms after waking up.
+        pub fn busy(&mut self, time: u32) {
+            self.charge(PowerState::Active, time);
+            self.accounted += time;
+            self.idle_since = self.accounted;
+            self.state = PowerState::Idle;
+        }
+
+        pub fn print_report(&self) {
+            println!(
+                "[REPORT] Power: {:.3} J, {}ms active, {}ms idle, {}ms standby, {} spin-ups ({}ms)",
+                self.stats.energy,
+                self.stats.active_time,
+                self.stats.idle_time,
+                self.stats.standby_time,
+                self.stats.spin_ups,
+                self.stats.spin_up_time
+            );
+        }
+    }
+
     pub struct Disk {
         pub tracks_num: u32,
         pub sectors_per_track: u32,
         pub next_track_seek_time: u32,
-        pub rewind_seek_time: u32,
         pub rotation_latency_time: u32,
-        pub sector_access_time: u32,
+        pub sector_access_time: u32, // Time to read one sector
+        pub write_access_time: u32,  // Time to write one sector
+        pub write_verify: bool,      // Re-read written sectors, costs an extra rotation
+        pub track_buffer: Option<TrackBuffer>,
+        pub power: Option<PowerModel>, // Always spinning at no cost when absent
     }
 
     impl Disk {
@@ -13,7 +247,6 @@ pub mod disk {
             tracks_num: u32,
             sectors_per_track: u32,
             next_track_seek_time: u32,
-            rewind_seek_time: u32,
             rotation_latency_time: u32,
             sector_access_time: u32,
         ) -> Self {
@@ -21,23 +254,125 @@ pub mod disk {
                 tracks_num,
                 sectors_per_track,
                 next_track_seek_time,
-                rewind_seek_time,
                 rotation_latency_time,
                 sector_access_time,
+                write_access_time: sector_access_time,
+                write_verify: false,
+                track_buffer: None,
+                power: None,
             }
         }
 
-        pub fn simulate_access(&self, track: u32, sector_offset: u32) -> u32 {
-            let seek_time = track * self.next_track_seek_time;
+        pub fn with_write_timing(mut self, write_access_time: u32, write_verify: bool) -> Self {
+            self.write_access_time = write_access_time;
+            self.write_verify = write_verify;
+            self
+        }
+
+        pub fn with_track_buffer(mut self, segments: usize) -> Self {
+            self.track_buffer = Some(TrackBuffer::new(segments));
+            self
+        }
+
+        pub fn with_power_management(mut self, power: PowerModel) -> Self {
+            self.power = Some(power);
+            self
+        }
+
+        /// The rotation latency is the average wait, half of a full rotation.
+        pub fn rotation_time(&self) -> u32 {
+            2 * self.rotation_latency_time
+        }
+
+        pub fn seek_time(&self, track: u32) -> u32 {
+            track * self.next_track_seek_time
+        }
+
+        pub fn simulate_access(
+            &mut self,
+            track: u32,
+            sector_offset: u32,
+            count: u32,
+            operation: IoOperation,
+        ) -> u32 {
+            if operation == IoOperation::Read && self.track_buffer.is_some() {
+                return self.buffered_read(track, sector_offset, count);
+            }
+            let last_track = self.last_track(track, sector_offset, count);
+            if let Some(buffer) = self.track_buffer.as_mut() {
+                for t in track..=last_track {
+                    buffer.invalidate(t);
+                }
+            }
+            let seek_time = self.seek_time(track);
             let rotational_latency = self.rotation_latency_time;
-            let sector_access_time = self.sector_access_time;
+            let (sector_access_time, verify_time) = match operation {
+                IoOperation::Read => (self.sector_access_time * count, 0),
+                IoOperation::Write if self.write_verify => {
+                    (self.write_access_time * count, self.rotation_time())
+                }
+                IoOperation::Write => (self.write_access_time * count, 0),
+                IoOperation::ZoneReset => (0, 0), // Nothing to transfer
+            };
 
             println!(
-                "[DISK] Simulating access: Track {}, Sector Offset {}. Seek time: {}ms, Rotation latency: {}ms, Access time: {}ms.",
-                track, sector_offset, seek_time, rotational_latency, sector_access_time
+                "[DISK] Simulating {:?}: Track {}, Sector Offset {}, {} sector(s). Seek time: {}ms, Rotation latency: {}ms, Access time: {}ms, Verify time: {}ms.",
+                operation, track, sector_offset, count, seek_time, rotational_latency, sector_access_time, verify_time
             );
 
-            seek_time + rotational_latency + sector_access_time
+            seek_time + rotational_latency + sector_access_time + verify_time
+        }
+
+        /// Reads through the track buffer. Buffered tracks only cost the transfer time,
+        /// a missing track is read whole in one full rotation, starting wherever the
+        /// head lands.
+        fn buffered_read(&mut self, track: u32, sector_offset: u32, count: u32) -> u32 {
+            let last_track = self.last_track(track, sector_offset, count);
+            let transfer_time = self.sector_access_time * count;
+            let mut media_time = 0;
+            let mut missed = false;
+            for t in track..=last_track {
+                let buffered = self
+                    .track_buffer
+                    .as_ref()
+                    .is_some_and(|buffer| buffer.contains(t));
+                if buffered {
+                    if let Some(buffer) = self.track_buffer.as_mut() {
+                        buffer.touch(t);
+                    }
+                    continue;
+                }
+                missed = true;
+                let seek_time = self.seek_time(t);
+                println!(
+                    "[DISK] Track buffer miss: reading track {}. Seek time: {}ms, Rotation time: {}ms.",
+                    t,
+                    seek_time,
+                    self.rotation_time()
+                );
+                media_time += seek_time + self.rotation_time();
+                if let Some(buffer) = self.track_buffer.as_mut() {
+                    buffer.insert(t);
+                }
+            }
+
+            if let Some(buffer) = self.track_buffer.as_mut() {
+                if missed {
+                    buffer.stats.misses += 1;
+                } else {
+                    buffer.stats.hits += 1;
+                    println!(
+                        "[DISK] Track buffer hit: Track {}, Sector Offset {}, {} sector(s). Transfer time: {}ms.",
+                        track, sector_offset, count, transfer_time
+                    );
+                }
+            }
+            media_time + transfer_time
+        }
+
+        /// Last track a transfer touches. An empty transfer stays on its first track.
+        fn last_track(&self, track: u32, sector_offset: u32, count: u32) -> u32 {
+            track + (sector_offset + count.max(1) - 1) / self.sectors_per_track
         }
 
         pub fn get_track_sector(&self, sector: u32) -> (u32, u32) {
@@ -46,4 +381,174 @@ pub mod disk {
             (track, sector_offset)
         }
     }
+
+    /// Rotating disk.
+    impl BlockDevice for Disk {
+        fn name(&self) -> &'static str {
+            "HDD"
+        }
+
+        fn capacity(&self) -> u32 {
+            self.tracks_num * self.sectors_per_track
+        }
+
+        fn access(
+            &mut self,
+            sector: u32,
+            count: u32,
+            operation: IoOperation,
+        ) -> Result<u32, DeviceError> {
+            if operation == IoOperation::ZoneReset {
+                return Err(DeviceError::NoZones);
+            }
+            let (track, sector_offset) = self.get_track_sector(sector);
+            Ok(self.simulate_access(track, sector_offset, count, operation))
+        }
+
+        fn track_buffer(&self) -> Option<&TrackBuffer> {
+            self.track_buffer.as_ref()
+        }
+
+        fn power(&mut self) -> Option<&mut PowerModel> {
+            self.power.as_mut()
+        }
+
+        fn print_report(&self) {
+            if let Some(buffer) = &self.track_buffer {
+                buffer.print_report();
+            }
+            if let Some(power) = &self.power {
+                power.print_report();
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn track_sector_at_track_boundaries() {
+            let disk = Disk::new(5, 100, 2, 4, 1);
+            assert_eq!(disk.get_track_sector(0), (0, 0));
+            assert_eq!(disk.get_track_sector(99), (0, 99));
+            assert_eq!(disk.get_track_sector(100), (1, 0));
+            assert_eq!(disk.get_track_sector(101), (1, 1));
+            assert_eq!(disk.get_track_sector(499), (4, 99));
+        }
+
+        #[test]
+        fn track_sector_past_the_last_track() {
+            // No range check, the track number simply runs past `tracks_num`
+            let disk = Disk::new(5, 100, 2, 4, 1);
+            assert_eq!(disk.get_track_sector(500), (5, 0));
+            assert_eq!(
+                disk.get_track_sector(u32::MAX),
+                (u32::MAX / 100, u32::MAX % 100)
+            );
+        }
+
+        #[test]
+        fn track_sector_with_one_sector_per_track() {
+            let disk = Disk::new(5, 1, 2, 4, 1);
+            assert_eq!(disk.get_track_sector(0), (0, 0));
+            assert_eq!(disk.get_track_sector(4), (4, 0));
+        }
+
+        #[test]
+        fn disk_spins_down_after_idle_timeout() {
+            let mut power = PowerModel::new(Some(100), 50);
+            power.idle_until(80);
+            assert_eq!(power.state, PowerState::Idle);
+            power.idle_until(300);
+            assert_eq!(power.state, PowerState::Standby);
+            assert_eq!(
+                (power.stats.idle_time, power.stats.standby_time),
+                (100, 200)
+            );
+
+            let mut always_on = PowerModel::new(None, 50);
+            always_on.idle_until(10_000);
+            assert_eq!(always_on.state, PowerState::Idle);
+        }
+
+        #[test]
+        fn spin_up_delays_only_the_first_access() {
+            let mut power = PowerModel::new(Some(100), 50);
+            assert_eq!(power.wake(300), 50);
+            power.busy(10);
+            assert_eq!(power.accounted, 360);
+            assert_eq!(power.wake(370), 0);
+            assert_eq!((power.stats.spin_ups, power.stats.spin_up_time), (1, 50));
+        }
+
+        #[test]
+        fn energy_adds_up_per_state() {
+            let mut power = PowerModel::new(Some(100), 50).with_power_draw(8.0, 5.0, 1.0, 20.0);
+            power.wake(300);
+            power.busy(10);
+            power.idle_until(400);
+            let stats = &power.stats;
+            assert_eq!(
+                (stats.active_time, stats.idle_time, stats.standby_time),
+                (10, 140, 200)
+            );
+            // 8W for 10ms, 5W for 140ms, 1W for 200ms, 20W for the 50ms spin-up
+            let expected = 0.08 + 0.7 + 0.2 + 1.0;
+            assert!((stats.energy - expected).abs() < 1e-9, "{}", stats.energy);
+        }
+
+        fn buffered_disk() -> Disk {
+            Disk::new(5, 100, 2, 4, 1).with_track_buffer(2)
+        }
+
+        fn read(disk: &mut Disk, sector: u32, count: u32) -> u32 {
+            disk.access(sector, count, IoOperation::Read).unwrap()
+        }
+
+        #[test]
+        fn track_buffer_serves_repeated_reads() {
+            // A miss seeks to track 1 (2ms) and reads it in a full rotation (8ms)
+            let mut disk = buffered_disk();
+            assert_eq!(read(&mut disk, 110, 2), 2 + 8 + 2);
+            assert_eq!(read(&mut disk, 150, 1), 1);
+            // Only track 2 has to come from the media
+            assert_eq!(read(&mut disk, 199, 2), 4 + 8 + 2);
+
+            let stats = &disk.track_buffer.as_ref().unwrap().stats;
+            assert_eq!((stats.hits, stats.misses, stats.tracks_read), (1, 2, 2));
+        }
+
+        #[test]
+        fn track_buffer_evicts_least_recently_used_track() {
+            let mut disk = buffered_disk();
+            read(&mut disk, 0, 1);
+            read(&mut disk, 100, 1);
+            read(&mut disk, 0, 1);
+            read(&mut disk, 200, 1);
+            let buffer = disk.track_buffer.as_ref().unwrap();
+            assert_eq!(buffer.segments, [2, 0]);
+            assert_eq!(buffer.stats.evictions, 1);
+        }
+
+        #[test]
+        fn writes_invalidate_buffered_tracks() {
+            let mut disk = buffered_disk();
+            read(&mut disk, 50, 1);
+            read(&mut disk, 150, 1);
+            disk.access(99, 2, IoOperation::Write).unwrap();
+            let buffer = disk.track_buffer.as_ref().unwrap();
+            assert!(buffer.segments.is_empty());
+            assert_eq!(buffer.stats.invalidations, 2);
+            assert_eq!(read(&mut disk, 50, 1), 8 + 1);
+        }
+
+        #[test]
+        fn empty_read_stays_on_its_track() {
+            let mut disk = buffered_disk();
+            assert_eq!(read(&mut disk, 0, 0), 8);
+            assert_eq!(read(&mut disk, 0, 0), 0);
+            assert_eq!(disk.track_buffer.as_ref().unwrap().segments, [0]);
+        }
+    }
 }
@@ -0,0 +1,28 @@
+pub mod golden {
+    use std::path::PathBuf;
+
+    /// Compares `actual` with `tests/golden/<name>.txt`. With `UPDATE_GOLDEN=1` set the
+    /// file is rewritten instead, review the diff before committing it.
+    pub fn assert_golden(name: &str, actual: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.txt", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, actual).expect("cannot write golden file");
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!(
+                "cannot read {}: {}, run with UPDATE_GOLDEN=1 to create it",
+                path.display(),
+                err
+            )
+        });
+        assert_eq!(
+            expected,
+            actual,
+            "output differs from {}, run with UPDATE_GOLDEN=1 if the change is intended",
+            path.display()
+        );
+    }
+}
@@ -1,114 +1,535 @@
+#![allow(clippy::module_inception)]
 
 mod cache;
+mod cpu;
+mod device;
 mod disk;
+#[cfg(test)]
+mod golden;
+mod policies;
 mod process;
+mod readahead;
 mod scheduler;
+mod ssd;
+mod stats;
 mod system;
+mod trace;
+mod volume;
+mod zoned;
 
-use cache::cache::LfuCache;
-use disk::disk::Disk;
+use cache::cache::{Aging, CachePolicy, LfuCache, WritePolicy};
+use cpu::cpu::{Cfs, CpuScheduler, Mlfq, PriorityScheduler, RoundRobin};
+use device::device::BlockDevice;
+use disk::disk::{Disk, PowerModel};
+use policies::policies::{ArcCache, ClockCache, FifoCache, LruCache, LruKCache, TwoQueueCache};
 use process::process::Process;
-use scheduler::scheduler::{FifoScheduler, IoOperation, Request};
+use scheduler::scheduler::Scheduler;
+use scheduler::scheduler::{
+    ClassAwareScheduler, FifoScheduler, FlookScheduler, IoClass, IoOperation, LookScheduler,
+    Request, ZoneAwareScheduler,
+};
+use ssd::ssd::Ssd;
+use std::collections::HashMap;
+use std::str::FromStr;
 use system::system::System;
-use crate::scheduler::scheduler::{FlookScheduler, LookScheduler};
+use trace::trace::{AddressMapping, Trace, TraceFormat, TraceMapping};
+use volume::volume::{RaidLevel, Volume};
+use zoned::zoned::{ZoneManagement, ZonedDisk};
+
+const MAX_REQUEST_SECTORS: u32 = 8;
+const CACHE_BUFFERS: usize = 6;
+const ZONE_SECTORS: u32 = 50;
+const MAX_OPEN_ZONES: usize = 4;
+
+struct Options {
+    scheduler: String,
+    cache: String,
+    write_policy: WritePolicy,
+    readahead_window: Option<u32>, // Maximum read-ahead window, disabled when absent or 0
+    track_buffer: Option<usize>,   // Drive track buffer segments, disabled when absent or 0
+    cpu_scheduler: String,
+    raid: RaidLevel,
+    disks: usize,
+    device: String, // hdd, ssd, smr (host-managed) or smr-drive (drive-managed)
+    idle_timeout: Option<u32>, // Disks spin down after this long without requests
+    trace: Option<(TraceFormat, String)>, // Replaces the built-in workload
+    address_mapping: AddressMapping,
+    time_scale: Option<f64>,
+    trace_max_sectors: Option<u32>, // Longer traced I/Os are cut short, no limit when absent
+    record: Option<String>,         // Trace file the run is recorded to
+}
+
+/// Flags taking a value, see `main` for what they accept.
+const FLAGS: [&str; 14] = [
+    "--scheduler",
+    "--write-policy",
+    "--cache",
+    "--readahead",
+    "--track-buffer",
+    "--cpu",
+    "--raid",
+    "--device",
+    "--spin-down",
+    "--trace",
+    "--address-mapping",
+    "--time-scale",
+    "--max-trace-sectors",
+    "--record",
+];
+
+/// Collects `--flag value` pairs. Unknown flags and flags without a value are errors.
+fn parse_flags(args: &[String]) -> Result<HashMap<&str, &str>, String> {
+    let mut flags = HashMap::new();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if !FLAGS.contains(&flag.as_str()) {
+            return Err(format!("unknown flag {:?}", flag));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        flags.insert(flag.as_str(), value.as_str());
+    }
+    Ok(flags)
+}
+
+/// Value of a flag that must be one of `choices`, the first one when the flag is absent.
+fn choice<'a>(
+    flags: &HashMap<&str, &'a str>,
+    name: &str,
+    choices: &[&'a str],
+) -> Result<&'a str, String> {
+    match flags.get(name) {
+        None => Ok(choices[0]),
+        Some(value) if choices.contains(value) => Ok(value),
+        Some(value) => Err(format!(
+            "{} {:?} is not one of {}",
+            name,
+            value,
+            choices.join(", ")
+        )),
+    }
+}
+
+/// Numeric value of a flag, `None` when the flag is absent.
+fn number<T: FromStr>(flags: &HashMap<&str, &str>, name: &str) -> Result<Option<T>, String> {
+    flags
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("{} {:?} is not a number", name, value))
+        })
+        .transpose()
+}
+
+/// Checks every flag value, unknown names and malformed numbers are errors.
+fn parse_options(flags: &HashMap<&str, &str>) -> Result<Options, String> {
+    let write_policy = match choice(
+        flags,
+        "--write-policy",
+        &["write-back", "write-through", "write-around"],
+    )? {
+        "write-through" => WritePolicy::WriteThrough,
+        "write-around" => WritePolicy::WriteAround,
+        _ => WritePolicy::WriteBack,
+    };
+    let (raid, disks) = match choice(flags, "--raid", &["single", "raid0", "raid1", "raid5"])? {
+        "raid0" => (RaidLevel::Raid0 { chunk_sectors: 4 }, 2),
+        "raid1" => (RaidLevel::Raid1, 2),
+        "raid5" => (RaidLevel::Raid5 { chunk_sectors: 4 }, 3),
+        _ => (RaidLevel::Single, 1),
+    };
+    let trace = match flags.get("--trace") {
+        Some(value) => {
+            let (format, path) = value
+                .split_once(':')
+                .ok_or_else(|| format!("--trace {:?} is not format:path", value))?;
+            let format = TraceFormat::from_name(format).ok_or_else(|| {
+                format!(
+                    "--trace format {:?} is not one of spc, msr, blkparse, replay",
+                    format
+                )
+            })?;
+            Some((format, path.to_string()))
+        }
+        None => None,
+    };
+    let address_mapping = match choice(flags, "--address-mapping", &["wrap", "scale"])? {
+        "scale" => AddressMapping::Scale,
+        _ => AddressMapping::Wrap,
+    };
+    // Replays keep the sectors of the recorded run, a mapping would be ignored.
+    if matches!(trace, Some((TraceFormat::Recorded, _))) && flags.contains_key("--address-mapping")
+    {
+        return Err("--address-mapping does not apply to a replay trace".to_string());
+    }
+    let time_scale = number::<f64>(flags, "--time-scale")?;
+    if let Some(time_scale) = time_scale.filter(|&n| n <= 0.0 || !n.is_finite()) {
+        return Err(format!("--time-scale {} must be positive", time_scale));
+    }
+    Ok(Options {
+        scheduler: choice(
+            flags,
+            "--scheduler",
+            &[
+                "flook",
+                "fifo",
+                "look",
+                "class-fifo",
+                "class-look",
+                "class-flook",
+                "zoned",
+            ],
+        )?
+        .to_string(),
+        cache: choice(
+            flags,
+            "--cache",
+            &[
+                "lfu",
+                "lfu-halve",
+                "lfu-decay",
+                "lfu-fbr",
+                "lru",
+                "fifo",
+                "clock",
+                "2q",
+                "arc",
+                "lru-k",
+            ],
+        )?
+        .to_string(),
+        write_policy,
+        readahead_window: number(flags, "--readahead")?.filter(|&n| n > 0),
+        track_buffer: number(flags, "--track-buffer")?.filter(|&n| n > 0),
+        cpu_scheduler: choice(flags, "--cpu", &["rr", "priority", "mlfq", "cfs"])?.to_string(),
+        raid,
+        disks,
+        device: choice(flags, "--device", &["hdd", "ssd", "smr", "smr-drive"])?.to_string(),
+        idle_timeout: number(flags, "--spin-down")?.filter(|&n| n > 0),
+        trace,
+        address_mapping,
+        time_scale,
+        trace_max_sectors: match number(flags, "--max-trace-sectors")? {
+            Some(0) => None,
+            Some(max) => Some(max),
+            None => Some(MAX_REQUEST_SECTORS),
+        },
+        record: flags.get("--record").map(|path| path.to_string()),
+    })
+}
 
 fn main() {
-    let disk = Disk::new(5, 100, 2, 10, 4, 1);
-    let cache = LfuCache::new(6, 2, 2);
-    let scheduler = FlookScheduler::new();
-    let mut system = System::new(scheduler, cache, disk, 20);
+    // Usage: cargo run -- [--scheduler fifo|look|flook|class-fifo|class-look|class-flook|zoned]
+    //                     [--write-policy write-back|write-through|write-around]
+    //                     [--cache lfu|lfu-halve|lfu-decay|lfu-fbr|lru|fifo|clock|2q|arc|lru-k]
+    //                     [--readahead max window] [--track-buffer segments]
+    //                     [--cpu rr|priority|mlfq|cfs] [--raid single|raid0|raid1|raid5]
+    //                     [--device hdd|ssd|smr|smr-drive] [--spin-down timeout]
+    //                     [--trace spc|msr|blkparse|replay:trace file] [--address-mapping wrap|scale]
+    //                     [--time-scale factor] [--max-trace-sectors count, 0 for no limit]
+    //                     [--record trace file]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_flags(&args).and_then(|flags| parse_options(&flags)) {
+        Ok(options) => options,
+        Err(err) => {
+            println!("[ERROR] Invalid arguments: {}", err);
+            return;
+        }
+    };
+
+    match options.scheduler.as_str() {
+        "fifo" => with_cache(FifoScheduler::new(MAX_REQUEST_SECTORS), &options),
+        "look" => with_cache(LookScheduler::new(MAX_REQUEST_SECTORS), &options),
+        "class-fifo" => with_cache(
+            ClassAwareScheduler::new(|| FifoScheduler::new(MAX_REQUEST_SECTORS)),
+            &options,
+        ),
+        "class-look" => with_cache(
+            ClassAwareScheduler::new(|| LookScheduler::new(MAX_REQUEST_SECTORS)),
+            &options,
+        ),
+        "class-flook" => with_cache(
+            ClassAwareScheduler::new(|| FlookScheduler::new(MAX_REQUEST_SECTORS)),
+            &options,
+        ),
+        "zoned" => with_cache(
+            ZoneAwareScheduler::new(
+                ZONE_SECTORS,
+                MAX_OPEN_ZONES,
+                MAX_REQUEST_SECTORS,
+                LookScheduler::new(MAX_REQUEST_SECTORS),
+            ),
+            &options,
+        ),
+        _ => with_cache(FlookScheduler::new(MAX_REQUEST_SECTORS), &options),
+    }
+}
+
+fn with_cache<S: Scheduler + Clone>(scheduler: S, options: &Options) {
+    match options.cache.as_str() {
+        "lru" => simulate(scheduler, LruCache::new(CACHE_BUFFERS), options),
+        "fifo" => simulate(scheduler, FifoCache::new(CACHE_BUFFERS), options),
+        "clock" => simulate(scheduler, ClockCache::new(CACHE_BUFFERS), options),
+        "2q" => simulate(scheduler, TwoQueueCache::new(CACHE_BUFFERS), options),
+        "arc" => simulate(scheduler, ArcCache::new(CACHE_BUFFERS), options),
+        "lru-k" => simulate(scheduler, LruKCache::new(CACHE_BUFFERS, 2), options),
+        name => {
+            let aging = match name {
+                "lfu-halve" => Aging::Halve { interval: 16 },
+                "lfu-decay" => Aging::Decay { half_life: 50 },
+                "lfu-fbr" => Aging::NoLeftHits,
+                _ => Aging::None,
+            };
+            let builder = LfuCache::builder(CACHE_BUFFERS).aging(aging);
+            // FBR sizes its sections relative to the whole cache
+            let builder = if aging == Aging::NoLeftHits {
+                builder.left_percent(25).middle_percent(50)
+            } else {
+                builder.left(2).middle(2)
+            };
+            match builder.build() {
+                Ok(cache) => simulate(scheduler, cache, options),
+                Err(err) => println!("[ERROR] Invalid cache configuration: {}", err),
+            }
+        }
+    }
+}
+
+fn simulate<S: Scheduler + Clone, C: CachePolicy>(scheduler: S, cache: C, options: &Options) {
+    let devices: Vec<Box<dyn BlockDevice>> = (0..options.disks)
+        .map(|_| -> Box<dyn BlockDevice> {
+            let disk = Disk::new(5, 100, 2, 4, 1)
+                .with_write_timing(2, true)
+                .with_power_management(
+                    PowerModel::new(options.idle_timeout, 50).with_power_draw(8.0, 5.0, 1.0, 20.0),
+                );
+            let disk = match options.track_buffer {
+                Some(segments) => disk.with_track_buffer(segments),
+                None => disk,
+            };
+            let management = match options.device.as_str() {
+                "ssd" => return Box::new(Ssd::new(2, 2, 24, 8).with_timing(1, 2, 10, 1)),
+                "smr" => ZoneManagement::HostManaged,
+                "smr-drive" => ZoneManagement::DriveManaged,
+                _ => return Box::new(disk),
+            };
+            Box::new(
+                ZonedDisk::new(disk, ZONE_SECTORS, MAX_OPEN_ZONES)
+                    .with_conventional_zones(1)
+                    .with_management(management),
+            )
+        })
+        .collect();
+    println!(
+        "[LOG] {} {}(s) of {} sectors.",
+        devices.len(),
+        devices[0].name(),
+        devices[0].capacity()
+    );
+    let volume = match Volume::new(options.raid, devices, scheduler) {
+        Ok(volume) => volume,
+        Err(err) => {
+            println!("[ERROR] Invalid volume configuration: {}", err);
+            return;
+        }
+    };
+    let processes = match &options.trace {
+        Some((format, path)) => match Trace::load(path, *format) {
+            Ok(trace) => {
+                println!(
+                    "[LOG] Loaded {} records of a {:?} trace from {}.",
+                    trace.records.len(),
+                    trace.format,
+                    path
+                );
+                let mut mapping = TraceMapping::new(volume.capacity())
+                    .with_address_mapping(options.address_mapping)
+                    .with_max_sectors(options.trace_max_sectors);
+                if let Some(time_scale) = options.time_scale {
+                    mapping = mapping.with_time_scale(time_scale);
+                }
+                trace.processes(&mapping, 20)
+            }
+            Err(err) => {
+                println!("[ERROR] Invalid trace {}: {}", path, err);
+                return;
+            }
+        },
+        None => demo_workload(),
+    };
+    let cpu_scheduler: Box<dyn CpuScheduler> = match options.cpu_scheduler.as_str() {
+        "priority" => Box::new(PriorityScheduler::new()),
+        "mlfq" => Box::new(Mlfq::new(3, 10, 100)),
+        "cfs" => Box::new(Cfs::new(40, 5)),
+        _ => Box::new(RoundRobin::new()),
+    };
+    let mut system = System::new(cache, volume)
+        .with_cpu_scheduler(cpu_scheduler)
+        .with_flusher(40, 80)
+        .with_write_policy(options.write_policy);
+    if let Some(max_window) = options.readahead_window {
+        system = system.with_readahead(2, max_window);
+    }
+    if let Some(path) = &options.record {
+        system = system.with_recorder(path);
+    }
+
+    for process in processes {
+        system.process_manager.add_process(process);
+    }
+
+    println!("[LOG] Starting system execution.");
+    system.run();
+    println!("[LOG] System execution completed.");
+}
 
+/// Six processes with overlapping sectors, a sequential reader and a mix of nice
+/// values and I/O classes.
+fn demo_workload() -> Vec<Process> {
     let process1 = Process::new(
         1,
         vec![
-            Request {
-                sector: 100,
-                operation: IoOperation::Read,
-            },
-            Request {
-                sector: 200,
-                operation: IoOperation::Write,
-            },
+            Request::new(100, IoOperation::Read),
+            Request::new(200, IoOperation::Write),
         ],
         20,
-    );
+    )
+    .with_cpu_bursts([5, 12]);
 
     let process2 = Process::new(
         2,
         vec![
-            Request {
-                sector: 100,
-                operation: IoOperation::Write, // Overlapping sector 100
-            },
-            Request {
-                sector: 300,
-                operation: IoOperation::Read,
-            },
+            Request::new(100, IoOperation::Write), // Overlapping sector 100
+            Request::new(300, IoOperation::Read),
         ],
         20,
-    );
+    )
+    .with_cpu_bursts([25, 3]);
 
     let process3 = Process::new(
         3,
         vec![
-            Request {
-                sector: 150,
-                operation: IoOperation::Read,
-            },
-            Request {
-                sector: 250,
-                operation: IoOperation::Write,
-            },
-            Request {
-                sector: 350,
-                operation: IoOperation::Read,
-            },
+            Request::new(150, IoOperation::Read),
+            Request::new(250, IoOperation::Write),
+            Request::new(350, IoOperation::Read),
         ],
         30,
-    );
+    )
+    .with_io_class(IoClass::BestEffort(2))
+    .with_think_times([0, 3, 5])
+    .with_cpu_bursts([2, 4, 6]);
 
     let process4 = Process::new(
         4,
         vec![
-            Request {
-                sector: 50,
-                operation: IoOperation::Write,
-            },
-            Request {
-                sector: 75,
-                operation: IoOperation::Read,
-            },
-            Request {
-                sector: 400,
-                operation: IoOperation::Write,
-            },
+            Request::new(50, IoOperation::Write),
+            Request::new(75, IoOperation::Read),
+            Request::new(400, IoOperation::Write),
         ],
         25,
-    );
+    )
+    .with_cpu_bursts([8, 3, 10]);
 
     let process5 = Process::new(
         5,
         vec![
-            Request {
-                sector: 20,
-                operation: IoOperation::Read,
-            },
-            Request {
-                sector: 500,
-                operation: IoOperation::Write,
-            },
+            Request::new(20, IoOperation::Read),
+            Request::new(500, IoOperation::Write),
         ],
         15,
-    );
+    )
+    .with_nice(-5)
+    .with_io_class(IoClass::RealTime)
+    .with_cpu_bursts([30, 5]);
+
+    // Sequential reader, its requests are merged in the scheduler queue and
+    // trigger read-ahead
+    let process6 = Process::new(
+        6,
+        (300..306)
+            .map(|sector| Request::new(sector, IoOperation::Read))
+            .collect(),
+        20,
+    )
+    .with_nice(10)
+    .with_io_class(IoClass::Idle)
+    .with_cpu_bursts([1; 6]);
 
+    vec![process1, process2, process3, process4, process5, process6]
+}
 
-    system.process_manager.add_process(process1);
-    system.process_manager.add_process(process2);
-    system.process_manager.add_process(process3);
-    system.process_manager.add_process(process4);
-    system.process_manager.add_process(process5);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("[LOG] Starting system execution.");
-    system.run();
-    println!("[LOG] System execution completed.");
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_flags(&args).and_then(|flags| parse_options(&flags))
+    }
+
+    #[test]
+    fn defaults_without_flags() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.scheduler, "flook");
+        assert_eq!(options.cache, "lfu");
+        assert_eq!(options.raid, RaidLevel::Single);
+        assert_eq!(options.trace_max_sectors, Some(MAX_REQUEST_SECTORS));
+        assert!(options.trace.is_none());
+    }
+
+    #[test]
+    fn unknown_values_name_the_flag() {
+        for (flag, value) in [
+            ("--scheduler", "sstf"),
+            ("--cache", "mru"),
+            ("--cpu", "edf"),
+            ("--device", "tape"),
+            ("--raid", "raid6"),
+            ("--write-policy", "write-never"),
+            ("--address-mapping", "hash"),
+        ] {
+            let err = parse(&[flag, value]).err().unwrap();
+            assert!(err.starts_with(&format!("{} {:?} is not one of", flag, value)));
+        }
+    }
+
+    #[test]
+    fn bad_numbers_are_rejected() {
+        assert_eq!(
+            parse(&["--readahead", "eight"]).err(),
+            Some("--readahead \"eight\" is not a number".to_string())
+        );
+        assert!(parse(&["--spin-down", "-5"]).is_err());
+        assert!(parse(&["--time-scale", "0"]).is_err());
+        assert_eq!(parse(&["--readahead", "0"]).unwrap().readahead_window, None);
+    }
+
+    #[test]
+    fn trace_needs_a_known_format_and_a_path() {
+        assert_eq!(
+            parse(&["--trace", "trace.csv"]).err(),
+            Some("--trace \"trace.csv\" is not format:path".to_string())
+        );
+        assert!(parse(&["--trace", "csv:trace.csv"])
+            .err()
+            .unwrap()
+            .starts_with("--trace format \"csv\""));
+        let options = parse(&["--trace", "msr:trace.csv"]).unwrap();
+        assert_eq!(
+            options.trace,
+            Some((TraceFormat::Msr, "trace.csv".to_string()))
+        );
+    }
+
+    #[test]
+    fn replay_rejects_address_mapping() {
+        assert_eq!(
+            parse(&["--trace", "replay:run.csv", "--address-mapping", "scale"]).err(),
+            Some("--address-mapping does not apply to a replay trace".to_string())
+        );
+        assert!(parse(&["--trace", "replay:run.csv"]).is_ok());
+        assert!(parse(&["--trace", "spc:run.csv", "--address-mapping", "scale"]).is_ok());
+    }
 }
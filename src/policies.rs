@@ -0,0 +1,774 @@
+pub mod policies {
+    use crate::cache::cache::{Buffer, CacheOutcome, CachePolicy, CacheStats};
+    use std::collections::VecDeque;
+
+    fn position(queue: &VecDeque<Buffer>, sector: u32) -> Option<usize> {
+        queue.iter().position(|b| b.sector == sector)
+    }
+
+    /// Least recently used. The front of the queue is the most recently used buffer.
+    pub struct LruCache {
+        pub queue: VecDeque<Buffer>,
+        pub capacity: usize,
+        pub stats: CacheStats,
+    }
+
+    impl LruCache {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                queue: VecDeque::new(),
+                capacity: capacity.max(1),
+                stats: CacheStats::default(),
+            }
+        }
+    }
+
+    impl CachePolicy for LruCache {
+        fn name(&self) -> &'static str {
+            "LRU"
+        }
+
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer> {
+            self.queue.iter_mut().find(|b| b.sector == sector)
+        }
+
+        fn access(&mut self, sector: u32) -> CacheOutcome {
+            if let Some(pos) = position(&self.queue, sector) {
+                let mut buffer = self.queue.remove(pos).unwrap();
+                buffer.counter += 1;
+                println!("[LRU] Buffer for sector {} moved to front.", sector);
+                self.queue.push_front(buffer);
+                return self.stats.hit("LRU");
+            }
+            self.stats.miss()
+        }
+
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer> {
+            println!("[LRU] Adding buffer for sector {}.", buffer.sector);
+            self.queue.push_front(buffer);
+            if self.queue.len() > self.capacity {
+                return self.evict();
+            }
+            None
+        }
+
+        fn evict(&mut self) -> Option<Buffer> {
+            let buffer = self.queue.pop_back()?;
+            self.stats.record_eviction(&buffer);
+            println!(
+                "[LRU] Buffer for sector {} evicted as least recently used.",
+                buffer.sector
+            );
+            Some(buffer)
+        }
+
+        fn contains(&self, sector: u32) -> bool {
+            position(&self.queue, sector).is_some()
+        }
+
+        fn remove(&mut self, sector: u32) -> Option<Buffer> {
+            position(&self.queue, sector).and_then(|pos| self.queue.remove(pos))
+        }
+
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_> {
+            Box::new(self.queue.iter_mut())
+        }
+
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)> {
+            vec![("LRU", self.queue.len())]
+        }
+
+        fn stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn stats_mut(&mut self) -> &mut CacheStats {
+            &mut self.stats
+        }
+    }
+
+    /// First in, first out. Hits do not change the eviction order.
+    pub struct FifoCache {
+        pub queue: VecDeque<Buffer>,
+        pub capacity: usize,
+        pub stats: CacheStats,
+    }
+
+    impl FifoCache {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                queue: VecDeque::new(),
+                capacity: capacity.max(1),
+                stats: CacheStats::default(),
+            }
+        }
+    }
+
+    impl CachePolicy for FifoCache {
+        fn name(&self) -> &'static str {
+            "FIFO"
+        }
+
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer> {
+            self.queue.iter_mut().find(|b| b.sector == sector)
+        }
+
+        fn access(&mut self, sector: u32) -> CacheOutcome {
+            if let Some(buffer) = self.lookup(sector) {
+                buffer.counter += 1;
+                return self.stats.hit("FIFO");
+            }
+            self.stats.miss()
+        }
+
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer> {
+            println!("[FIFO] Adding buffer for sector {}.", buffer.sector);
+            self.queue.push_back(buffer);
+            if self.queue.len() > self.capacity {
+                return self.evict();
+            }
+            None
+        }
+
+        fn evict(&mut self) -> Option<Buffer> {
+            let buffer = self.queue.pop_front()?;
+            self.stats.record_eviction(&buffer);
+            println!(
+                "[FIFO] Buffer for sector {} evicted as oldest.",
+                buffer.sector
+            );
+            Some(buffer)
+        }
+
+        fn contains(&self, sector: u32) -> bool {
+            position(&self.queue, sector).is_some()
+        }
+
+        fn remove(&mut self, sector: u32) -> Option<Buffer> {
+            position(&self.queue, sector).and_then(|pos| self.queue.remove(pos))
+        }
+
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_> {
+            Box::new(self.queue.iter_mut())
+        }
+
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)> {
+            vec![("FIFO", self.queue.len())]
+        }
+
+        fn stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn stats_mut(&mut self) -> &mut CacheStats {
+            &mut self.stats
+        }
+    }
+
+    /// Second chance: the hand skips buffers referenced since it last passed them.
+    pub struct ClockCache {
+        pub buffers: Vec<Buffer>,
+        pub referenced: Vec<bool>,
+        pub hand: usize,
+        pub capacity: usize,
+        pub stats: CacheStats,
+    }
+
+    impl ClockCache {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                buffers: Vec::new(),
+                referenced: Vec::new(),
+                hand: 0,
+                capacity: capacity.max(1),
+                stats: CacheStats::default(),
+            }
+        }
+
+        fn position(&self, sector: u32) -> Option<usize> {
+            self.buffers.iter().position(|b| b.sector == sector)
+        }
+    }
+
+    impl CachePolicy for ClockCache {
+        fn name(&self) -> &'static str {
+            "CLOCK"
+        }
+
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer> {
+            self.buffers.iter_mut().find(|b| b.sector == sector)
+        }
+
+        fn access(&mut self, sector: u32) -> CacheOutcome {
+            if let Some(pos) = self.position(sector) {
+                self.buffers[pos].counter += 1;
+                self.referenced[pos] = true;
+                return self.stats.hit("CLOCK");
+            }
+            self.stats.miss()
+        }
+
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer> {
+            let evicted = if self.buffers.len() >= self.capacity {
+                self.evict()
+            } else {
+                None
+            };
+            println!("[CLOCK] Adding buffer for sector {}.", buffer.sector);
+            // New buffers go right behind the hand, so they are inspected last
+            self.buffers.insert(self.hand, buffer);
+            self.referenced.insert(self.hand, false);
+            self.hand = (self.hand + 1) % self.buffers.len();
+            evicted
+        }
+
+        fn evict(&mut self) -> Option<Buffer> {
+            if self.buffers.is_empty() {
+                return None;
+            }
+            while self.referenced[self.hand] {
+                self.referenced[self.hand] = false;
+                self.hand = (self.hand + 1) % self.buffers.len();
+            }
+            let buffer = self.buffers.remove(self.hand);
+            self.referenced.remove(self.hand);
+            self.stats.record_eviction(&buffer);
+            if self.hand >= self.buffers.len() {
+                self.hand = 0;
+            }
+            println!(
+                "[CLOCK] Buffer for sector {} evicted by the clock hand.",
+                buffer.sector
+            );
+            Some(buffer)
+        }
+
+        fn contains(&self, sector: u32) -> bool {
+            self.position(sector).is_some()
+        }
+
+        fn remove(&mut self, sector: u32) -> Option<Buffer> {
+            let pos = self.position(sector)?;
+            self.referenced.remove(pos);
+            if pos < self.hand {
+                self.hand -= 1;
+            }
+            let buffer = self.buffers.remove(pos);
+            if self.hand >= self.buffers.len() {
+                self.hand = 0;
+            }
+            Some(buffer)
+        }
+
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_> {
+            Box::new(self.buffers.iter_mut())
+        }
+
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)> {
+            vec![("CLOCK", self.buffers.len())]
+        }
+
+        fn stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn stats_mut(&mut self) -> &mut CacheStats {
+            &mut self.stats
+        }
+    }
+
+    /// 2Q: new buffers wait in the `a1_in` FIFO, sectors referenced again after leaving it
+    /// (remembered in the `a1_out` ghost queue) are promoted to the `am` LRU queue.
+    pub struct TwoQueueCache {
+        pub a1_in: VecDeque<Buffer>,
+        pub a1_out: VecDeque<u32>,
+        pub am: VecDeque<Buffer>,
+        pub kin: usize,  // Size of the a1_in queue, a quarter of the cache
+        pub kout: usize, // Remembered sectors in a1_out, half of the cache
+        pub capacity: usize,
+        pub stats: CacheStats,
+    }
+
+    impl TwoQueueCache {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                a1_in: VecDeque::new(),
+                a1_out: VecDeque::new(),
+                am: VecDeque::new(),
+                kin: (capacity / 4).max(1),
+                kout: (capacity / 2).max(1),
+                capacity: capacity.max(1),
+                stats: CacheStats::default(),
+            }
+        }
+    }
+
+    impl CachePolicy for TwoQueueCache {
+        fn name(&self) -> &'static str {
+            "2Q"
+        }
+
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer> {
+            self.am
+                .iter_mut()
+                .chain(self.a1_in.iter_mut())
+                .find(|b| b.sector == sector)
+        }
+
+        fn access(&mut self, sector: u32) -> CacheOutcome {
+            if let Some(pos) = position(&self.am, sector) {
+                let mut buffer = self.am.remove(pos).unwrap();
+                buffer.counter += 1;
+                self.am.push_front(buffer);
+                return self.stats.hit("Am");
+            }
+            // Correlated references while in a1_in do not promote the buffer
+            if let Some(buffer) = self.a1_in.iter_mut().find(|b| b.sector == sector) {
+                buffer.counter += 1;
+                return self.stats.hit("A1in");
+            }
+            self.stats.miss()
+        }
+
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer> {
+            let evicted = if self.am.len() + self.a1_in.len() >= self.capacity {
+                self.evict()
+            } else {
+                None
+            };
+
+            if let Some(pos) = self.a1_out.iter().position(|&s| s == buffer.sector) {
+                self.a1_out.remove(pos);
+                self.stats.promotions += 1;
+                println!("[2Q] Sector {} seen before, adding to Am.", buffer.sector);
+                self.am.push_front(buffer);
+            } else {
+                println!("[2Q] Adding buffer for sector {} to A1in.", buffer.sector);
+                self.a1_in.push_front(buffer);
+            }
+            evicted
+        }
+
+        fn evict(&mut self) -> Option<Buffer> {
+            let buffer = if self.a1_in.len() > self.kin || self.am.is_empty() {
+                let buffer = self.a1_in.pop_back()?;
+                self.a1_out.push_front(buffer.sector);
+                if self.a1_out.len() > self.kout {
+                    self.a1_out.pop_back();
+                }
+                buffer
+            } else {
+                self.am.pop_back()?
+            };
+            self.stats.record_eviction(&buffer);
+            println!("[2Q] Buffer for sector {} evicted.", buffer.sector);
+            Some(buffer)
+        }
+
+        fn contains(&self, sector: u32) -> bool {
+            position(&self.am, sector).is_some() || position(&self.a1_in, sector).is_some()
+        }
+
+        fn remove(&mut self, sector: u32) -> Option<Buffer> {
+            if let Some(pos) = position(&self.am, sector) {
+                return self.am.remove(pos);
+            }
+            position(&self.a1_in, sector).and_then(|pos| self.a1_in.remove(pos))
+        }
+
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_> {
+            Box::new(self.am.iter_mut().chain(self.a1_in.iter_mut()))
+        }
+
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)> {
+            vec![("A1in", self.a1_in.len()), ("Am", self.am.len())]
+        }
+
+        fn stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn stats_mut(&mut self) -> &mut CacheStats {
+            &mut self.stats
+        }
+    }
+
+    /// Adaptive replacement cache. `t1` holds buffers seen once, `t2` buffers seen at least
+    /// twice, `b1` and `b2` remember sectors recently evicted from them. Hits in the ghost
+    /// lists move the target size `p` of `t1`.
+    pub struct ArcCache {
+        pub t1: VecDeque<Buffer>,
+        pub t2: VecDeque<Buffer>,
+        pub b1: VecDeque<u32>,
+        pub b2: VecDeque<u32>,
+        pub p: usize,
+        pub capacity: usize,
+        pub stats: CacheStats,
+    }
+
+    impl ArcCache {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                t1: VecDeque::new(),
+                t2: VecDeque::new(),
+                b1: VecDeque::new(),
+                b2: VecDeque::new(),
+                p: 0,
+                capacity: capacity.max(1),
+                stats: CacheStats::default(),
+            }
+        }
+
+        /// Evicts from `t1` or `t2` depending on the target size, remembering the sector.
+        fn replace(&mut self, in_b2: bool) -> Option<Buffer> {
+            let from_t1 = !self.t1.is_empty()
+                && (self.t1.len() > self.p || (in_b2 && self.t1.len() == self.p));
+            let buffer = if from_t1 || self.t2.is_empty() {
+                let buffer = self.t1.pop_back()?;
+                self.b1.push_front(buffer.sector);
+                buffer
+            } else {
+                let buffer = self.t2.pop_back()?;
+                self.b2.push_front(buffer.sector);
+                buffer
+            };
+            self.stats.record_eviction(&buffer);
+            println!("[ARC] Buffer for sector {} evicted.", buffer.sector);
+            Some(buffer)
+        }
+
+        fn is_full(&self) -> bool {
+            self.t1.len() + self.t2.len() >= self.capacity
+        }
+    }
+
+    impl CachePolicy for ArcCache {
+        fn name(&self) -> &'static str {
+            "ARC"
+        }
+
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer> {
+            self.t1
+                .iter_mut()
+                .chain(self.t2.iter_mut())
+                .find(|b| b.sector == sector)
+        }
+
+        fn access(&mut self, sector: u32) -> CacheOutcome {
+            let (buffer, segment) = if let Some(pos) = position(&self.t1, sector) {
+                (self.t1.remove(pos), "T1")
+            } else if let Some(pos) = position(&self.t2, sector) {
+                (self.t2.remove(pos), "T2")
+            } else {
+                return self.stats.miss();
+            };
+            let mut buffer = buffer.unwrap();
+            buffer.counter += 1;
+            self.t2.push_front(buffer);
+            if segment == "T1" {
+                self.stats.promotions += 1;
+            }
+            self.stats.hit(segment)
+        }
+
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer> {
+            let sector = buffer.sector;
+
+            if let Some(pos) = self.b1.iter().position(|&s| s == sector) {
+                let delta = (self.b2.len() / self.b1.len()).max(1);
+                self.p = (self.p + delta).min(self.capacity);
+                self.b1.remove(pos);
+                let evicted = if self.is_full() {
+                    self.replace(false)
+                } else {
+                    None
+                };
+                self.stats.promotions += 1;
+                println!("[ARC] Sector {} found in B1, adding to T2.", sector);
+                self.t2.push_front(buffer);
+                return evicted;
+            }
+
+            if let Some(pos) = self.b2.iter().position(|&s| s == sector) {
+                let delta = (self.b1.len() / self.b2.len()).max(1);
+                self.p = self.p.saturating_sub(delta);
+                self.b2.remove(pos);
+                let evicted = if self.is_full() {
+                    self.replace(true)
+                } else {
+                    None
+                };
+                self.stats.promotions += 1;
+                println!("[ARC] Sector {} found in B2, adding to T2.", sector);
+                self.t2.push_front(buffer);
+                return evicted;
+            }
+
+            let mut evicted = None;
+            if self.t1.len() + self.b1.len() >= self.capacity {
+                if self.t1.len() < self.capacity {
+                    self.b1.pop_back();
+                    if self.is_full() {
+                        evicted = self.replace(false);
+                    }
+                } else {
+                    evicted = self.t1.pop_back();
+                    if let Some(buffer) = &evicted {
+                        self.stats.record_eviction(buffer);
+                    }
+                }
+            } else {
+                let total = self.t1.len() + self.t2.len() + self.b1.len() + self.b2.len();
+                if total >= 2 * self.capacity {
+                    self.b2.pop_back();
+                }
+                if self.is_full() {
+                    evicted = self.replace(false);
+                }
+            }
+            println!("[ARC] Adding buffer for sector {} to T1.", sector);
+            self.t1.push_front(buffer);
+            evicted
+        }
+
+        fn evict(&mut self) -> Option<Buffer> {
+            self.replace(false)
+        }
+
+        fn contains(&self, sector: u32) -> bool {
+            position(&self.t1, sector).is_some() || position(&self.t2, sector).is_some()
+        }
+
+        fn remove(&mut self, sector: u32) -> Option<Buffer> {
+            if let Some(pos) = position(&self.t1, sector) {
+                return self.t1.remove(pos);
+            }
+            position(&self.t2, sector).and_then(|pos| self.t2.remove(pos))
+        }
+
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_> {
+            Box::new(self.t1.iter_mut().chain(self.t2.iter_mut()))
+        }
+
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)> {
+            vec![("T1", self.t1.len()), ("T2", self.t2.len())]
+        }
+
+        fn stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn stats_mut(&mut self) -> &mut CacheStats {
+            &mut self.stats
+        }
+    }
+
+    /// LRU-K: evicts the buffer whose K-th most recent access is the oldest. Buffers with
+    /// fewer than K accesses go first, the least recently used of them.
+    pub struct LruKCache {
+        pub buffers: Vec<Buffer>,
+        pub history: Vec<VecDeque<u64>>, // Last K access ticks per buffer, newest first
+        pub k: usize,
+        pub tick: u64,
+        pub capacity: usize,
+        pub stats: CacheStats,
+    }
+
+    impl LruKCache {
+        pub fn new(capacity: usize, k: usize) -> Self {
+            Self {
+                buffers: Vec::new(),
+                history: Vec::new(),
+                k: k.max(1),
+                tick: 0,
+                capacity: capacity.max(1),
+                stats: CacheStats::default(),
+            }
+        }
+
+        fn position(&self, sector: u32) -> Option<usize> {
+            self.buffers.iter().position(|b| b.sector == sector)
+        }
+
+        fn record(&mut self, pos: usize) {
+            self.tick += 1;
+            let history = &mut self.history[pos];
+            history.push_front(self.tick);
+            history.truncate(self.k);
+        }
+    }
+
+    impl CachePolicy for LruKCache {
+        fn name(&self) -> &'static str {
+            "LRU-K"
+        }
+
+        fn lookup(&mut self, sector: u32) -> Option<&mut Buffer> {
+            self.buffers.iter_mut().find(|b| b.sector == sector)
+        }
+
+        fn access(&mut self, sector: u32) -> CacheOutcome {
+            if let Some(pos) = self.position(sector) {
+                self.buffers[pos].counter += 1;
+                self.record(pos);
+                return self.stats.hit("LRU-K");
+            }
+            self.stats.miss()
+        }
+
+        fn insert(&mut self, buffer: Buffer) -> Option<Buffer> {
+            let evicted = if self.buffers.len() >= self.capacity {
+                self.evict()
+            } else {
+                None
+            };
+            println!("[LRU-K] Adding buffer for sector {}.", buffer.sector);
+            self.buffers.push(buffer);
+            self.history.push(VecDeque::new());
+            self.record(self.buffers.len() - 1);
+            evicted
+        }
+
+        fn evict(&mut self) -> Option<Buffer> {
+            // Missing K-th access sorts first, then the oldest K-th and last accesses
+            let k = self.k;
+            let victim = self
+                .history
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, history)| {
+                    (history.len() >= k, history.get(k - 1).copied(), history[0])
+                })
+                .map(|(pos, _)| pos)?;
+            self.history.remove(victim);
+            let buffer = self.buffers.remove(victim);
+            self.stats.record_eviction(&buffer);
+            println!(
+                "[LRU-K] Buffer for sector {} evicted by backward {}-distance.",
+                buffer.sector, self.k
+            );
+            Some(buffer)
+        }
+
+        fn contains(&self, sector: u32) -> bool {
+            self.position(sector).is_some()
+        }
+
+        fn remove(&mut self, sector: u32) -> Option<Buffer> {
+            let pos = self.position(sector)?;
+            self.history.remove(pos);
+            Some(self.buffers.remove(pos))
+        }
+
+        fn buffers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Buffer> + '_> {
+            Box::new(self.buffers.iter_mut())
+        }
+
+        fn segment_sizes(&self) -> Vec<(&'static str, usize)> {
+            vec![("LRU-K", self.buffers.len())]
+        }
+
+        fn stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn stats_mut(&mut self) -> &mut CacheStats {
+            &mut self.stats
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn sector(evicted: Option<Buffer>) -> Option<u32> {
+            evicted.map(|buffer| buffer.sector)
+        }
+
+        #[test]
+        fn zero_capacity_keeps_one_buffer() {
+            let caches: Vec<Box<dyn CachePolicy>> = vec![
+                Box::new(LruCache::new(0)),
+                Box::new(FifoCache::new(0)),
+                Box::new(ClockCache::new(0)),
+                Box::new(TwoQueueCache::new(0)),
+                Box::new(ArcCache::new(0)),
+                Box::new(LruKCache::new(0, 2)),
+            ];
+            for mut cache in caches {
+                assert_eq!(sector(cache.fill(1)), None, "{}", cache.name());
+                assert!(cache.contains(1), "{}", cache.name());
+                assert_eq!(sector(cache.fill(2)), Some(1), "{}", cache.name());
+                assert!(cache.contains(2), "{}", cache.name());
+            }
+        }
+
+        #[test]
+        fn clock_gives_referenced_buffers_a_second_chance() {
+            let mut cache = ClockCache::new(3);
+            for s in 1..=3 {
+                cache.fill(s);
+            }
+            cache.access(1);
+            // The hand clears the reference of 1 and takes 2, then 3 on the next round
+            assert_eq!(sector(cache.fill(4)), Some(2));
+            assert_eq!(sector(cache.fill(5)), Some(3));
+            assert!(cache.contains(1));
+            assert_eq!(sector(cache.fill(6)), Some(1));
+        }
+
+        #[test]
+        fn two_queue_promotes_sectors_seen_after_leaving_a1in() {
+            // A1in holds one buffer beyond which it gives buffers up first
+            let mut cache = TwoQueueCache::new(4);
+            for s in 1..=4 {
+                cache.fill(s);
+            }
+            cache.access(1);
+            assert_eq!(cache.stats.promotions, 0);
+            assert_eq!(sector(cache.fill(5)), Some(1));
+            assert_eq!(cache.a1_out, [1]);
+
+            assert_eq!(sector(cache.fill(1)), Some(2));
+            assert_eq!(cache.stats.promotions, 1);
+            assert_eq!(position(&cache.am, 1), Some(0));
+            assert_eq!(cache.segment_sizes(), [("A1in", 3), ("Am", 1)]);
+        }
+
+        #[test]
+        fn arc_ghost_hits_move_the_target_size() {
+            let mut cache = ArcCache::new(2);
+            cache.fill(1);
+            cache.access(1);
+            cache.fill(2);
+            assert_eq!(sector(cache.fill(3)), Some(2));
+            assert_eq!(cache.b1, [2]);
+
+            // A hit in B1 grows T1, so T2 gives up a buffer
+            assert_eq!(sector(cache.fill(2)), Some(1));
+            assert_eq!(cache.p, 1);
+            assert_eq!(cache.b2, [1]);
+
+            // A hit in B2 shrinks T1 again
+            assert_eq!(sector(cache.fill(1)), Some(3));
+            assert_eq!(cache.p, 0);
+            assert_eq!(cache.segment_sizes(), [("T1", 0), ("T2", 2)]);
+            assert_eq!(cache.stats.promotions, 3);
+        }
+
+        #[test]
+        fn lru_k_evicts_buffers_with_fewer_than_k_references_first() {
+            let mut cache = LruKCache::new(3, 2);
+            cache.fill(1);
+            cache.fill(2);
+            cache.access(1);
+            cache.access(2);
+            cache.fill(3);
+            // 3 is the most recently used but has a single reference
+            assert_eq!(sector(cache.fill(4)), Some(3));
+            cache.access(4);
+            // All have two references now, 1 has the oldest second to last one
+            assert_eq!(sector(cache.fill(5)), Some(1));
+        }
+    }
+}
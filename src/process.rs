@@ -1,79 +1,433 @@
 pub mod process {
-    use crate::scheduler::scheduler::Request;
-    use std::collections::VecDeque;
+    use crate::cpu::cpu::CpuScheduler;
+    use crate::scheduler::scheduler::{IoClass, Request, RequestOrigin};
+    use std::collections::{BTreeMap, VecDeque};
+    use std::fmt;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ProcessState {
+        Ready,
+        Running,
+        BlockedOnIo(Request), // Waiting for the disk to serve this request
+        Sleeping,             // Thinking until `wake_at`
+        Terminated,
+    }
+
+    impl fmt::Display for ProcessState {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ProcessState::Ready => write!(f, "ready"),
+                ProcessState::Running => write!(f, "running"),
+                ProcessState::BlockedOnIo(request) => write!(
+                    f,
+                    "blocked on {:?} of sector {}",
+                    request.operation, request.sector
+                ),
+                ProcessState::Sleeping => write!(f, "sleeping"),
+                ProcessState::Terminated => write!(f, "terminated"),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ProcessError {
+        UnknownProcess(u32),
+        IllegalTransition {
+            process_id: u32,
+            from: ProcessState,
+            to: ProcessState,
+        },
+    }
+
+    impl fmt::Display for ProcessError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ProcessError::UnknownProcess(process_id) => {
+                    write!(f, "process {} does not exist", process_id)
+                }
+                ProcessError::IllegalTransition {
+                    process_id,
+                    from,
+                    to,
+                } => write!(
+                    f,
+                    "process {} cannot go from {} to {}",
+                    process_id, from, to
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for ProcessError {}
+
+    /// One request of a workload with the time the process spends before issuing it.
+    #[derive(Debug)]
+    pub struct Step {
+        pub request: Request,
+        pub think_time: u32, // Sleeping, off the CPU
+        pub cpu_burst: u32,  // Computing on the CPU, after the think time
+    }
 
     #[derive(Debug)]
     pub struct Process {
         pub id: u32,
-        pub requests: Vec<Request>,
-        pub time_remaining: u32, // Time remaining in current quantum
+        pub steps: VecDeque<Step>,     // Workload in program order
+        pub quantum: u32,              // Time slice under round-robin and priority scheduling
+        pub time_slice: u32,           // Length of the current time slice
+        pub time_remaining: u32,       // CPU time remaining in the current time slice
+        pub nice: i32,                 // -20 (most favored) to 19
+        pub io_class: Option<IoClass>, // Derived from the nice value when not set
+        pub state: ProcessState,
+        pub wake_at: u32,  // End of the current think time while sleeping
+        pub cpu_time: u32, // CPU time used so far
     }
 
     impl Process {
         pub fn new(id: u32, requests: Vec<Request>, quantum_time: u32) -> Self {
+            // A zero quantum would preempt the process before it could do anything
+            let quantum_time = quantum_time.max(1);
             Self {
                 id,
-                requests,
+                steps: requests
+                    .into_iter()
+                    .map(|request| Step {
+                        request,
+                        think_time: 0,
+                        cpu_burst: 0,
+                    })
+                    .collect(),
+                quantum: quantum_time,
+                time_slice: quantum_time,
                 time_remaining: quantum_time,
+                nice: 0,
+                io_class: None,
+                state: ProcessState::Ready,
+                wake_at: 0,
+                cpu_time: 0,
+            }
+        }
+
+        pub fn with_nice(mut self, nice: i32) -> Self {
+            self.nice = nice.clamp(-20, 19);
+            self
+        }
+
+        pub fn with_io_class(mut self, io_class: IoClass) -> Self {
+            self.io_class = Some(io_class);
+            self
+        }
+
+        /// I/O class stamped on every request of the process.
+        pub fn io_class(&self) -> IoClass {
+            self.io_class
+                .unwrap_or_else(|| IoClass::from_nice(self.nice))
+        }
+
+        /// Sets the think time spent before each request, in order. Requests past the
+        /// end of `think_times` are issued without thinking.
+        pub fn with_think_times(mut self, think_times: impl IntoIterator<Item = u32>) -> Self {
+            for (step, time) in self.steps.iter_mut().zip(think_times) {
+                step.think_time = time;
             }
+            self
+        }
+
+        /// Sets the CPU time computed before each request, in order.
+        pub fn with_cpu_bursts(mut self, cpu_bursts: impl IntoIterator<Item = u32>) -> Self {
+            for (step, burst) in self.steps.iter_mut().zip(cpu_bursts) {
+                step.cpu_burst = burst;
+            }
+            self
         }
 
         pub fn has_requests(&self) -> bool {
-            !self.requests.is_empty()
+            !self.steps.is_empty()
         }
 
+        /// Think time before the next request. It is only spent once.
+        pub fn take_think_time(&mut self) -> u32 {
+            self.steps
+                .front_mut()
+                .map_or(0, |step| std::mem::take(&mut step.think_time))
+        }
+
+        /// CPU time still to compute before the next request.
+        pub fn cpu_burst(&self) -> u32 {
+            self.steps.front().map_or(0, |step| step.cpu_burst)
+        }
+
+        /// Computes part of the current burst.
+        pub fn compute(&mut self, time: u32) {
+            if let Some(step) = self.steps.front_mut() {
+                step.cpu_burst = step.cpu_burst.saturating_sub(time);
+            }
+            self.charge_cpu(time);
+        }
+
+        /// Charges CPU time to the quantum.
+        pub fn charge_cpu(&mut self, time: u32) {
+            self.time_remaining = self.time_remaining.saturating_sub(time);
+            self.cpu_time += time;
+        }
+
+        /// Next request in program order.
         pub fn next_request(&mut self) -> Option<Request> {
-            self.requests.pop()
+            self.steps.pop_front().map(|step| step.request)
         }
     }
 
+    /// Owns every process for its whole life. Ready processes wait in the CPU
+    /// scheduler and sleeping ones in `sleep_q`, all state changes go through
+    /// `transition`.
     pub struct ProcessManager {
-        pub run_q: VecDeque<Process>,
-        pub sleep_q: VecDeque<Process>,
+        pub processes: BTreeMap<u32, Process>,
+        pub cpu_scheduler: Box<dyn CpuScheduler>,
+        pub sleep_q: VecDeque<u32>,
     }
 
     impl ProcessManager {
-        pub fn new() -> Self {
+        pub fn new(cpu_scheduler: Box<dyn CpuScheduler>) -> Self {
             Self {
-                run_q: VecDeque::new(),
+                processes: BTreeMap::new(),
+                cpu_scheduler,
                 sleep_q: VecDeque::new(),
             }
         }
 
-        pub fn add_process(&mut self, process: Process) {
-            self.run_q.push_back(process);
+        pub fn add_process(&mut self, mut process: Process) {
+            process.state = ProcessState::Ready;
+            self.cpu_scheduler.enqueue(&process);
+            self.processes.insert(process.id, process);
         }
 
-        pub fn move_to_sleep(&mut self, process_id: u32) {
-            if let Some(pos) = self.run_q.iter().position(|p| p.id == process_id) {
-                let process = self.run_q.remove(pos).unwrap();
-                println!(
-                    "[ProcessManager] Moving process {} to sleep queue.",
-                    process.id
-                );
-                self.sleep_q.push_back(process);
-            } else {
-                println!(
-                    "[ProcessManager] Process {} not found in run queue.",
-                    process_id
-                );
+        pub fn get_mut(&mut self, process_id: u32) -> Option<&mut Process> {
+            self.processes.get_mut(&process_id)
+        }
+
+        /// True while some process has not terminated.
+        pub fn has_live_processes(&self) -> bool {
+            self.processes
+                .values()
+                .any(|process| process.state != ProcessState::Terminated)
+        }
+
+        pub fn blocked_processes(&self) -> usize {
+            self.processes
+                .values()
+                .filter(|process| matches!(process.state, ProcessState::BlockedOnIo(_)))
+                .count()
+        }
+
+        fn is_legal(from: &ProcessState, to: &ProcessState) -> bool {
+            use ProcessState::*;
+            matches!(
+                (from, to),
+                (Ready, Running)
+                    | (Running, Ready)
+                    | (Running, BlockedOnIo(_))
+                    | (Running, Sleeping)
+                    | (Running, Terminated)
+                    | (BlockedOnIo(_), Ready)
+                    | (Sleeping, Ready)
+            )
+        }
+
+        /// Moves a process to a new state and keeps the queues in step with it.
+        pub fn transition(
+            &mut self,
+            process_id: u32,
+            to: ProcessState,
+        ) -> Result<(), ProcessError> {
+            let process = self
+                .processes
+                .get_mut(&process_id)
+                .ok_or(ProcessError::UnknownProcess(process_id))?;
+            if !Self::is_legal(&process.state, &to) {
+                return Err(ProcessError::IllegalTransition {
+                    process_id,
+                    from: process.state.clone(),
+                    to,
+                });
+            }
+
+            match process.state {
+                ProcessState::Ready => self.cpu_scheduler.remove(process_id),
+                ProcessState::Running => {
+                    let used = process.time_slice - process.time_remaining;
+                    self.cpu_scheduler
+                        .account(process, used, process.time_remaining == 0);
+                }
+                ProcessState::Sleeping => self.sleep_q.retain(|&id| id != process_id),
+                _ => {}
+            }
+            process.state = to;
+            match process.state {
+                ProcessState::Ready => self.cpu_scheduler.enqueue(process),
+                ProcessState::Sleeping => self.sleep_q.push_back(process_id),
+                _ => {}
             }
+            Ok(())
         }
 
-        pub fn wake_up_process(&mut self, process_id: u32) {
-            if let Some(pos) = self.sleep_q.iter().position(|p| p.id == process_id) {
-                let process = self.sleep_q.remove(pos).unwrap();
-                println!(
-                    "[ProcessManager] Waking up process {} and moving to run queue.",
-                    process.id
-                );
-                self.run_q.push_back(process);
-            } else {
-                println!(
-                    "[ProcessManager] Process {} not found in sleep queue.",
-                    process_id
-                );
+        /// Picks the next ready process, marks it running and hands it a time slice.
+        pub fn schedule(&mut self) -> Option<u32> {
+            let process_id = self.cpu_scheduler.pick_next()?;
+            if let Err(err) = self.transition(process_id, ProcessState::Running) {
+                println!("[ERROR] {}", err);
+                return None;
             }
+            let process = self.processes.get_mut(&process_id)?;
+            process.time_slice = self.cpu_scheduler.time_slice(process);
+            process.time_remaining = process.time_slice;
+            Some(process_id)
+        }
+
+        pub fn sleep(&mut self, process_id: u32, wake_at: u32) -> Result<(), ProcessError> {
+            self.transition(process_id, ProcessState::Sleeping)?;
+            if let Some(process) = self.processes.get_mut(&process_id) {
+                process.wake_at = wake_at;
+            }
+            Ok(())
+        }
+
+        /// Wakes the sleeping processes whose think time is over, returns their ids.
+        pub fn wake_sleepers(&mut self, now: u32) -> Vec<u32> {
+            let due: Vec<u32> = self
+                .sleep_q
+                .iter()
+                .copied()
+                .filter(|id| self.processes[id].wake_at <= now)
+                .collect();
+            due.into_iter()
+                .filter(|&process_id| self.make_ready(process_id))
+                .collect()
+        }
+
+        /// Moves a sleeping or blocked process back to the ready queue, reporting
+        /// an illegal transition instead of failing.
+        fn make_ready(&mut self, process_id: u32) -> bool {
+            match self.transition(process_id, ProcessState::Ready) {
+                Ok(()) => true,
+                Err(err) => {
+                    println!("[ERROR] {}", err);
+                    false
+                }
+            }
+        }
+
+        /// Earliest time a sleeping process wakes up.
+        pub fn next_wake_time(&self) -> Option<u32> {
+            self.sleep_q
+                .iter()
+                .map(|id| self.processes[id].wake_at)
+                .min()
+        }
+
+        /// True if `completed` is the request the process is blocked on, or a prefetch
+        /// of the sector a blocked read waits for. Writebacks never complete a process
+        /// request, even for the same sector.
+        fn is_served_by(process_id: u32, blocked: &Request, completed: &Request) -> bool {
+            let covered = blocked.operation == completed.operation
+                && (completed.sector..completed.end()).contains(&blocked.sector);
+            covered
+                && match completed.origin {
+                    RequestOrigin::Process(id) => id == process_id,
+                    RequestOrigin::Prefetch => true,
+                    RequestOrigin::Writeback => false,
+                }
+        }
+
+        /// Unblocks the processes waiting for a request the volume just finished: the
+        /// process that issued it, or for a prefetch the readers waiting for its sectors.
+        /// Returns the requests they were blocked on.
+        pub fn complete_io(&mut self, completed: &Request) -> Vec<Request> {
+            let done: Vec<(u32, Request)> = self
+                .processes
+                .values()
+                .filter_map(|process| match &process.state {
+                    ProcessState::BlockedOnIo(request)
+                        if Self::is_served_by(process.id, request, completed) =>
+                    {
+                        Some((process.id, request.clone()))
+                    }
+                    _ => None,
+                })
+                .collect();
+            done.into_iter()
+                .filter(|&(process_id, _)| self.make_ready(process_id))
+                .map(|(_, request)| request)
+                .collect()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::cpu::cpu::RoundRobin;
+        use crate::scheduler::scheduler::IoOperation;
+
+        /// Process 1 blocked on a write of sector 7, process 2 on a read of sector 8.
+        fn blocked() -> ProcessManager {
+            let mut manager = ProcessManager::new(Box::new(RoundRobin::new()));
+            for (process_id, sector, operation) in
+                [(1, 7, IoOperation::Write), (2, 8, IoOperation::Read)]
+            {
+                let request = Request {
+                    origin: RequestOrigin::Process(process_id),
+                    ..Request::new(sector, operation)
+                };
+                manager.add_process(Process::new(process_id, vec![request.clone()], 10));
+                assert_eq!(manager.schedule(), Some(process_id));
+                manager
+                    .transition(process_id, ProcessState::BlockedOnIo(request))
+                    .unwrap();
+            }
+            manager
+        }
+
+        #[test]
+        fn schedule_refuses_process_that_is_not_ready() {
+            let mut manager = blocked();
+            let process = &manager.processes[&1];
+            manager.cpu_scheduler.enqueue(process);
+            assert_eq!(manager.schedule(), None);
+            assert!(matches!(
+                manager.processes[&1].state,
+                ProcessState::BlockedOnIo(_)
+            ));
+        }
+
+        #[test]
+        fn writeback_does_not_complete_process_write() {
+            let mut manager = blocked();
+            assert!(manager.complete_io(&Request::writeback(7, 0)).is_empty());
+            assert_eq!(manager.blocked_processes(), 2);
+
+            let own = Request {
+                origin: RequestOrigin::Process(1),
+                ..Request::new(7, IoOperation::Write)
+            };
+            let done = manager.complete_io(&own);
+            assert_eq!(done.len(), 1);
+            assert_eq!(manager.processes[&1].state, ProcessState::Ready);
+        }
+
+        #[test]
+        fn only_issuer_or_prefetch_completes_read() {
+            let mut manager = blocked();
+            let other = Request {
+                origin: RequestOrigin::Process(3),
+                ..Request::new(8, IoOperation::Read)
+            };
+            assert!(manager.complete_io(&other).is_empty());
+
+            let done = manager.complete_io(&Request::prefetch(6, 4, 0));
+            assert_eq!(done.len(), 1);
+            assert_eq!(manager.processes[&2].state, ProcessState::Ready);
+            assert!(matches!(
+                manager.processes[&1].state,
+                ProcessState::BlockedOnIo(_)
+            ));
         }
     }
 }
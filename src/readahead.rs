@@ -0,0 +1,162 @@
+pub mod readahead {
+    use std::collections::HashMap;
+
+    /// Sequential read stream of one process.
+    #[derive(Debug)]
+    pub struct Stream {
+        pub last_sector: u32,
+        pub run_length: u32,       // Consecutive sequential reads so far
+        pub window: u32,           // Sectors to keep prefetched ahead of the reader
+        pub prefetched_until: u32, // First sector after the prefetched range
+    }
+
+    #[derive(Debug, Default)]
+    pub struct ReadAheadStats {
+        pub requests: u32,  // Prefetch requests queued for the disk
+        pub sectors: u32,   // Sectors prefetched
+        pub useful: u32,    // Prefetched sectors later read by a process
+        pub wasted: u32,    // Prefetched sectors evicted without being read
+        pub disk_time: u32, // Disk time spent on prefetching
+        pub window_grows: u32,
+        pub window_shrinks: u32,
+    }
+
+    /// Detects sequential reads per process and decides which sectors to prefetch.
+    /// The window of a stream doubles when its prefetched sectors get read and halves
+    /// when they are evicted unused.
+    pub struct ReadAhead {
+        pub streams: HashMap<u32, Stream>, // Process id -> stream
+        pub pending: HashMap<u32, u32>,    // Sector queued for prefetch -> process id
+        pub prefetched: HashMap<u32, u32>, // Sector prefetched into the cache, not read yet -> process id
+        pub min_window: u32,
+        pub max_window: u32,
+        pub stats: ReadAheadStats,
+    }
+
+    impl ReadAhead {
+        pub fn new(min_window: u32, max_window: u32) -> Self {
+            Self {
+                streams: HashMap::new(),
+                pending: HashMap::new(),
+                prefetched: HashMap::new(),
+                min_window: min_window.max(1),
+                max_window: max_window.max(min_window),
+                stats: ReadAheadStats::default(),
+            }
+        }
+
+        /// Tracks a read issued by a process. Returns the range `(start, count)` to prefetch
+        /// once the reads look sequential.
+        pub fn on_read(&mut self, process_id: u32, sector: u32) -> Option<(u32, u32)> {
+            let min_window = self.min_window;
+            let stream = self.streams.entry(process_id).or_insert(Stream {
+                last_sector: sector,
+                run_length: 0,
+                window: min_window,
+                prefetched_until: 0,
+            });
+
+            if stream.run_length > 0 && sector == stream.last_sector + 1 {
+                stream.run_length += 1;
+            } else if stream.run_length == 0 || sector != stream.last_sector {
+                stream.run_length = 1;
+                stream.prefetched_until = 0;
+            }
+            stream.last_sector = sector;
+
+            if stream.run_length < 2 {
+                return None;
+            }
+            let start = stream.prefetched_until.max(sector + 1);
+            let end = sector + 1 + stream.window;
+            if start >= end {
+                return None;
+            }
+            stream.prefetched_until = end;
+            Some((start, end - start))
+        }
+
+        pub fn is_tracked(&self, sector: u32) -> bool {
+            self.pending.contains_key(&sector) || self.prefetched.contains_key(&sector)
+        }
+
+        /// Records a prefetch request queued for the disk.
+        pub fn on_queued(&mut self, process_id: u32, sector: u32, count: u32) {
+            self.stats.requests += 1;
+            self.stats.sectors += count;
+            for sector in sector..sector + count {
+                self.pending.insert(sector, process_id);
+            }
+        }
+
+        /// A prefetched sector arrived in the cache.
+        pub fn on_fill(&mut self, sector: u32) {
+            if let Some(process_id) = self.pending.remove(&sector) {
+                self.prefetched.insert(sector, process_id);
+            }
+        }
+
+        /// A process read a sector, `cached` tells whether the cache had it. Returns true
+        /// if it was prefetched and is in the cache or still on its way.
+        pub fn on_hit(&mut self, sector: u32, cached: bool) -> bool {
+            let process_id = match self.pending.remove(&sector) {
+                Some(process_id) => process_id,
+                None => match self.prefetched.remove(&sector) {
+                    Some(process_id) if cached => process_id,
+                    _ => return false,
+                },
+            };
+            self.stats.useful += 1;
+            if let Some(stream) = self.streams.get_mut(&process_id) {
+                if stream.window < self.max_window {
+                    stream.window = (stream.window * 2).min(self.max_window);
+                    self.stats.window_grows += 1;
+                }
+            }
+            true
+        }
+
+        /// The prefetch of a sector failed, it will not arrive in the cache.
+        pub fn on_failed(&mut self, sector: u32) {
+            self.pending.remove(&sector);
+        }
+
+        /// A sector left the cache. Counts it as wasted if it was prefetched and never read.
+        pub fn on_evict(&mut self, sector: u32) {
+            let Some(process_id) = self.prefetched.remove(&sector) else {
+                return;
+            };
+            self.stats.wasted += 1;
+            if let Some(stream) = self.streams.get_mut(&process_id) {
+                if stream.window > self.min_window {
+                    stream.window = (stream.window / 2).max(self.min_window);
+                    self.stats.window_shrinks += 1;
+                }
+            }
+        }
+
+        pub fn print_report(&self) {
+            let judged = self.stats.useful + self.stats.wasted;
+            let accuracy = if judged == 0 {
+                0.0
+            } else {
+                100.0 * self.stats.useful as f64 / judged as f64
+            };
+            println!(
+                "[REPORT] Read-ahead: {} requests, {} sectors prefetched, {}ms of disk time",
+                self.stats.requests, self.stats.sectors, self.stats.disk_time
+            );
+            println!(
+                "[REPORT] Read-ahead: {} used, {} wasted, {} unused in cache ({:.1}% accuracy)",
+                self.stats.useful,
+                self.stats.wasted,
+                self.prefetched.len(),
+                accuracy
+            );
+            println!(
+                "[REPORT] Read-ahead: window grew {} times, shrank {} times",
+                self.stats.window_grows, self.stats.window_shrinks
+            );
+        }
+    }
+}
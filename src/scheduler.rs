@@ -1,83 +1,290 @@
 pub mod scheduler {
-    use std::collections::VecDeque;
+    use std::collections::{BTreeMap, VecDeque};
+    use std::fmt;
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum IoOperation {
         Read,
         Write,
+        ZoneReset, // Drops the data of the zone holding the sector, on zoned devices
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RequestOrigin {
+        Process(u32), // Issued by the process with this id
+        Writeback,    // Dirty cache data written back by eviction or the flusher
+        Prefetch,     // Sectors read ahead of a sequential reader
+    }
+
+    /// I/O priority class, in the style of ionice. The derived order is the service
+    /// order: realtime first, then best-effort by level (0 highest), idle last.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum IoClass {
+        RealTime,
+        BestEffort(u8), // Level 0-7
+        Idle,
+    }
+
+    impl IoClass {
+        pub const BEST_EFFORT_LEVELS: u8 = 8;
+
+        /// Best-effort level a process gets from its nice value when no class is set.
+        pub fn from_nice(nice: i32) -> Self {
+            IoClass::BestEffort(((nice.clamp(-20, 19) + 20) / 5) as u8)
+        }
+    }
+
+    impl Default for IoClass {
+        fn default() -> Self {
+            IoClass::BestEffort(4)
+        }
+    }
+
+    impl fmt::Display for IoClass {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                IoClass::RealTime => write!(f, "realtime"),
+                IoClass::BestEffort(level) => write!(f, "best-effort/{}", level),
+                IoClass::Idle => write!(f, "idle"),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct Request {
         pub sector: u32,
+        pub count: u32, // Number of consecutive sectors, grows when requests are merged
         pub operation: IoOperation,
+        pub issued_at: u32, // Time the request was issued, the earliest one for merged requests
+        pub origin: RequestOrigin,
+        pub io_class: IoClass,
+    }
+
+    impl Request {
+        pub fn new(sector: u32, operation: IoOperation) -> Self {
+            Self {
+                sector,
+                count: 1,
+                operation,
+                issued_at: 0,
+                origin: RequestOrigin::Process(0),
+                io_class: IoClass::default(),
+            }
+        }
+
+        pub fn writeback(sector: u32, issued_at: u32) -> Self {
+            Self {
+                issued_at,
+                origin: RequestOrigin::Writeback,
+                ..Self::new(sector, IoOperation::Write)
+            }
+        }
+
+        pub fn prefetch(sector: u32, count: u32, issued_at: u32) -> Self {
+            Self {
+                count,
+                issued_at,
+                origin: RequestOrigin::Prefetch,
+                ..Self::new(sector, IoOperation::Read)
+            }
+        }
+
+        /// First sector after the end of the request.
+        pub fn end(&self) -> u32 {
+            self.sector + self.count
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MergeKind {
+        Front, // New request placed in front of a queued one
+        Back,  // New request appended to the end of a queued one
     }
 
     pub trait Scheduler {
-        fn add_request(&mut self, request: Request);
+        /// Queues a request. Returns how it was merged, if it was merged into a queued request.
+        fn add_request(&mut self, request: Request) -> Option<MergeKind>;
         fn get_next_request(&mut self, current_sector: u32) -> Option<Request>;
+        fn has_requests(&self) -> bool;
         fn print_queue_status(&self);
     }
 
+    /// A queue of requests that merges can join and remove entries of.
+    pub trait MergeQueue {
+        fn requests_mut(&mut self) -> &mut [Request];
+        fn remove_at(&mut self, index: usize);
+    }
+
+    impl MergeQueue for Vec<Request> {
+        fn requests_mut(&mut self) -> &mut [Request] {
+            self
+        }
+
+        fn remove_at(&mut self, index: usize) {
+            self.remove(index);
+        }
+    }
+
+    impl MergeQueue for VecDeque<Request> {
+        fn requests_mut(&mut self) -> &mut [Request] {
+            self.make_contiguous()
+        }
+
+        fn remove_at(&mut self, index: usize) {
+            self.remove(index);
+        }
+    }
+
+    /// Merges `request` into `queued` if it touches one end of it.
+    fn merge_into(
+        queued: &mut Request,
+        request: &Request,
+        max_sectors: u32,
+        boundary: Option<u32>,
+    ) -> Option<MergeKind> {
+        if queued.operation != request.operation
+            || queued.io_class != request.io_class
+            || queued.count + request.count > max_sectors
+        {
+            return None;
+        }
+        let start = queued.sector.min(request.sector);
+        let last = queued.end().max(request.end()) - 1;
+        if boundary.is_some_and(|boundary| start / boundary != last / boundary) {
+            return None;
+        }
+
+        let kind = if queued.end() == request.sector {
+            MergeKind::Back
+        } else if request.end() == queued.sector {
+            queued.sector = request.sector;
+            MergeKind::Front
+        } else {
+            return None;
+        };
+        queued.count += request.count;
+        queued.issued_at = queued.issued_at.min(request.issued_at);
+        Some(kind)
+    }
+
+    /// Tries to merge `request` into one of the queued requests touching adjacent sectors.
+    /// A request filling the gap between two queued requests joins them into one.
+    /// Reads are never merged with writes, requests of different I/O classes are never
+    /// merged and the merged size never exceeds `max_sectors`. With a `boundary`, no
+    /// merged request crosses a multiple of it.
+    pub fn try_merge(
+        queue: &mut impl MergeQueue,
+        request: &Request,
+        max_sectors: u32,
+        boundary: Option<u32>,
+    ) -> Option<MergeKind> {
+        let requests = queue.requests_mut();
+        let (index, kind) = requests
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, queued)| {
+                merge_into(queued, request, max_sectors, boundary).map(|kind| (index, kind))
+            })?;
+
+        let merged = requests[index].clone();
+        let joins = |other: usize| {
+            let mut queued = requests[other].clone();
+            other != index && merge_into(&mut queued, &merged, max_sectors, boundary).is_some()
+        };
+        if let Some(other) = (0..requests.len()).find(|&other| joins(other)) {
+            // The one queued first stays, so FIFO order holds
+            let (keep, drop) = (index.min(other), index.max(other));
+            let dropped = requests[drop].clone();
+            merge_into(&mut requests[keep], &dropped, max_sectors, boundary);
+            queue.remove_at(drop);
+        }
+        Some(kind)
+    }
+
+    /// OUT is towards higher sectors, IN towards lower ones.
+    fn direction_name(out: bool) -> &'static str {
+        if out {
+            "OUT"
+        } else {
+            "IN"
+        }
+    }
+
+    #[derive(Clone)]
     pub struct LookScheduler {
         pub queue: Vec<Request>,
         pub direction: bool,
+        pub max_request_sectors: u32,
     }
 
     impl LookScheduler {
-        pub fn new() -> Self {
+        pub fn new(max_request_sectors: u32) -> Self {
             Self {
                 queue: Vec::new(),
                 direction: true,
+                max_request_sectors,
             }
         }
     }
 
     impl Scheduler for LookScheduler {
-        fn add_request(&mut self, request: Request) {
+        fn add_request(&mut self, request: Request) -> Option<MergeKind> {
+            if let Some(kind) = try_merge(&mut self.queue, &request, self.max_request_sectors, None)
+            {
+                println!(
+                    "[LOOK] {:?}-merged request for sector {} ({:?})",
+                    kind, request.sector, request.operation
+                );
+                self.queue.sort_by_key(|req| req.sector);
+                return Some(kind);
+            }
+
             println!(
                 "[LOOK] Adding request for sector {} ({:?})",
                 request.sector, request.operation
             );
             self.queue.push(request);
             self.queue.sort_by_key(|req| req.sector);
+            None
         }
 
         fn get_next_request(&mut self, current_sector: u32) -> Option<Request> {
-            if self.direction {
-                if let Some(pos) = self
-                    .queue
-                    .iter()
-                    .position(|req| req.sector >= current_sector)
-                {
+            // Nothing left ahead of the head, it turns around and serves the other side
+            for _ in 0..2 {
+                let pos = if self.direction {
+                    self.queue
+                        .iter()
+                        .position(|req| req.sector >= current_sector)
+                } else {
+                    self.queue
+                        .iter()
+                        .rposition(|req| req.sector <= current_sector)
+                };
+                if let Some(pos) = pos {
                     let request = self.queue.remove(pos);
                     println!(
-                        "[LOOK] Serving request at sector {} moving OUT",
-                        request.sector
+                        "[LOOK] Serving request at sector {} moving {}",
+                        request.sector,
+                        direction_name(self.direction)
                     );
                     return Some(request);
                 }
-                self.direction = false;
-                println!("[LOOK] Changing direction to IN");
-            }
-
-            if let Some(pos) = self
-                .queue
-                .iter()
-                .rposition(|req| req.sector <= current_sector)
-            {
-                let request = self.queue.remove(pos);
+                if self.queue.is_empty() {
+                    return None;
+                }
+                self.direction = !self.direction;
                 println!(
-                    "[LOOK] Serving request at sector {} moving IN",
-                    request.sector
+                    "[LOOK] Changing direction to {}",
+                    direction_name(self.direction)
                 );
-                return Some(request);
             }
-            self.direction = true;
-            println!("[LOOK] Changing direction to OUT");
             None
         }
 
+        fn has_requests(&self) -> bool {
+            !self.queue.is_empty()
+        }
+
         fn print_queue_status(&self) {
             if self.queue.is_empty() {
                 println!("[LOOK] Queue is empty.");
@@ -85,9 +292,10 @@ pub mod scheduler {
                 println!("[LOOK] Current queue status:");
                 for (i, request) in self.queue.iter().enumerate() {
                     println!(
-                        "  [{}] Sector {} ({:?})",
+                        "  [{}] Sector {} x{} ({:?})",
                         i + 1,
                         request.sector,
+                        request.count,
                         request.operation
                     );
                 }
@@ -95,27 +303,45 @@ pub mod scheduler {
         }
     }
 
+    #[derive(Clone)]
     pub struct FlookScheduler {
         pub active_queue: Vec<Request>,
         pub waiting_queue: Vec<Request>,
         pub direction: bool, // true for OUT (increasing), false for IN (decreasing)
+        pub max_request_sectors: u32,
     }
     impl FlookScheduler {
-        pub fn new() -> Self {
+        pub fn new(max_request_sectors: u32) -> Self {
             Self {
                 active_queue: Vec::new(),
                 waiting_queue: Vec::new(),
                 direction: true,
+                max_request_sectors,
             }
         }
     }
     impl Scheduler for FlookScheduler {
-        fn add_request(&mut self, request: Request) {
+        fn add_request(&mut self, request: Request) -> Option<MergeKind> {
+            // Only the waiting queue accepts merges, the active queue is frozen
+            if let Some(kind) = try_merge(
+                &mut self.waiting_queue,
+                &request,
+                self.max_request_sectors,
+                None,
+            ) {
+                println!(
+                    "[FLOOK] {:?}-merged request for sector {} ({:?})",
+                    kind, request.sector, request.operation
+                );
+                return Some(kind);
+            }
+
             println!(
                 "[FLOOK] Adding request for sector {} ({:?})",
                 request.sector, request.operation
             );
             self.waiting_queue.push(request);
+            None
         }
 
         fn get_next_request(&mut self, current_sector: u32) -> Option<Request> {
@@ -125,40 +351,41 @@ pub mod scheduler {
                 self.active_queue.sort_by_key(|req| req.sector);
             }
 
-            if self.direction {
-                if let Some(pos) = self
-                    .active_queue
-                    .iter()
-                    .position(|req| req.sector >= current_sector)
-                {
+            for _ in 0..2 {
+                let pos = if self.direction {
+                    self.active_queue
+                        .iter()
+                        .position(|req| req.sector >= current_sector)
+                } else {
+                    self.active_queue
+                        .iter()
+                        .rposition(|req| req.sector <= current_sector)
+                };
+                if let Some(pos) = pos {
                     let request = self.active_queue.remove(pos);
                     println!(
-                        "[FLOOK] Serving request at sector {} moving OUT",
-                        request.sector
+                        "[FLOOK] Serving request at sector {} moving {}",
+                        request.sector,
+                        direction_name(self.direction)
                     );
                     return Some(request);
                 }
-                self.direction = false;
-                println!("[FLOOK] Changing direction to IN");
-            }
-
-            if let Some(pos) = self
-                .active_queue
-                .iter()
-                .rposition(|req| req.sector <= current_sector)
-            {
-                let request = self.active_queue.remove(pos);
+                if self.active_queue.is_empty() {
+                    return None;
+                }
+                self.direction = !self.direction;
                 println!(
-                    "[FLOOK] Serving request at sector {} moving IN",
-                    request.sector
+                    "[FLOOK] Changing direction to {}",
+                    direction_name(self.direction)
                 );
-                return Some(request);
             }
-            self.direction = true;
-            println!("[FLOOK] Changing direction to OUT");
             None
         }
 
+        fn has_requests(&self) -> bool {
+            !self.active_queue.is_empty() || !self.waiting_queue.is_empty()
+        }
+
         fn print_queue_status(&self) {
             println!("[FLOOK] Active queue:");
             if self.active_queue.is_empty() {
@@ -166,9 +393,10 @@ pub mod scheduler {
             } else {
                 for (i, request) in self.active_queue.iter().enumerate() {
                     println!(
-                        "  [{}] Sector {} ({:?})",
+                        "  [{}] Sector {} x{} ({:?})",
                         i + 1,
                         request.sector,
+                        request.count,
                         request.operation
                     );
                 }
@@ -180,9 +408,10 @@ pub mod scheduler {
             } else {
                 for (i, request) in self.waiting_queue.iter().enumerate() {
                     println!(
-                        "  [{}] Sector {} ({:?})",
+                        "  [{}] Sector {} x{} ({:?})",
                         i + 1,
                         request.sector,
+                        request.count,
                         request.operation
                     );
                 }
@@ -190,25 +419,38 @@ pub mod scheduler {
         }
     }
 
+    #[derive(Clone)]
     pub struct FifoScheduler {
         queue: VecDeque<Request>,
+        max_request_sectors: u32,
     }
 
     impl FifoScheduler {
-        pub fn new() -> Self {
+        pub fn new(max_request_sectors: u32) -> Self {
             Self {
                 queue: VecDeque::new(),
+                max_request_sectors,
             }
         }
     }
 
     impl Scheduler for FifoScheduler {
-        fn add_request(&mut self, request: Request) {
+        fn add_request(&mut self, request: Request) -> Option<MergeKind> {
+            if let Some(kind) = try_merge(&mut self.queue, &request, self.max_request_sectors, None)
+            {
+                println!(
+                    "[FIFO] {:?}-merged request for sector {} ({:?})",
+                    kind, request.sector, request.operation
+                );
+                return Some(kind);
+            }
+
             println!(
                 "[FIFO] Adding request for sector {} ({:?})",
                 request.sector, request.operation
             );
             self.queue.push_back(request);
+            None
         }
 
         fn get_next_request(&mut self, _current_sector: u32) -> Option<Request> {
@@ -223,6 +465,11 @@ pub mod scheduler {
                 None
             }
         }
+
+        fn has_requests(&self) -> bool {
+            !self.queue.is_empty()
+        }
+
         fn print_queue_status(&self) {
             if self.queue.is_empty() {
                 println!("[FIFO] Queue is empty.");
@@ -230,13 +477,633 @@ pub mod scheduler {
                 println!("[FIFO] Current queue status:");
                 for (i, request) in self.queue.iter().enumerate() {
                     println!(
-                        "  [{}] Sector {} ({:?})",
+                        "  [{}] Sector {} x{} ({:?})",
                         i + 1,
                         request.sector,
+                        request.count,
                         request.operation
                     );
                 }
             }
         }
     }
+
+    /// Wraps one scheduler per I/O class. Realtime requests are always served first.
+    /// Best-effort levels share the disk in turns, level `n` gets `8 - n` dispatches per
+    /// turn. Idle requests are only served when nothing else is queued.
+    #[derive(Clone)]
+    pub struct ClassAwareScheduler<S: Scheduler> {
+        pub realtime: S,
+        pub best_effort: Vec<S>, // One per level
+        pub idle: S,
+        pub current_level: usize,
+        pub turn_remaining: u32, // Dispatches left for the current best-effort level
+    }
+
+    impl<S: Scheduler> ClassAwareScheduler<S> {
+        pub fn new(mut make: impl FnMut() -> S) -> Self {
+            Self {
+                realtime: make(),
+                best_effort: (0..IoClass::BEST_EFFORT_LEVELS).map(|_| make()).collect(),
+                idle: make(),
+                current_level: 0,
+                turn_remaining: IoClass::BEST_EFFORT_LEVELS as u32,
+            }
+        }
+
+        fn level_share(level: usize) -> u32 {
+            IoClass::BEST_EFFORT_LEVELS as u32 - level as u32
+        }
+
+        fn next_best_effort(&mut self, current_sector: u32) -> Option<Request> {
+            let levels = self.best_effort.len();
+            if self.turn_remaining == 0 || !self.best_effort[self.current_level].has_requests() {
+                let next = (1..=levels)
+                    .map(|step| (self.current_level + step) % levels)
+                    .find(|&level| self.best_effort[level].has_requests())?;
+                self.current_level = next;
+                self.turn_remaining = Self::level_share(next);
+                println!("[CLASS] Best-effort level {} gets the disk.", next);
+            }
+            self.turn_remaining -= 1;
+            self.best_effort[self.current_level].get_next_request(current_sector)
+        }
+    }
+
+    impl<S: Scheduler> Scheduler for ClassAwareScheduler<S> {
+        fn add_request(&mut self, request: Request) -> Option<MergeKind> {
+            println!(
+                "[CLASS] Request for sector {} is {}.",
+                request.sector, request.io_class
+            );
+            match request.io_class {
+                IoClass::RealTime => self.realtime.add_request(request),
+                IoClass::BestEffort(level) => {
+                    let level = (level as usize).min(self.best_effort.len() - 1);
+                    self.best_effort[level].add_request(request)
+                }
+                IoClass::Idle => self.idle.add_request(request),
+            }
+        }
+
+        fn get_next_request(&mut self, current_sector: u32) -> Option<Request> {
+            if self.realtime.has_requests() {
+                return self.realtime.get_next_request(current_sector);
+            }
+            if self.best_effort.iter().any(|queue| queue.has_requests()) {
+                return self.next_best_effort(current_sector);
+            }
+            self.idle.get_next_request(current_sector)
+        }
+
+        fn has_requests(&self) -> bool {
+            self.realtime.has_requests()
+                || self.best_effort.iter().any(|queue| queue.has_requests())
+                || self.idle.has_requests()
+        }
+
+        fn print_queue_status(&self) {
+            if self.realtime.has_requests() {
+                println!("[CLASS] Realtime:");
+                self.realtime.print_queue_status();
+            }
+            for (level, queue) in self.best_effort.iter().enumerate() {
+                if queue.has_requests() {
+                    println!("[CLASS] Best-effort level {}:", level);
+                    queue.print_queue_status();
+                }
+            }
+            if self.idle.has_requests() {
+                println!("[CLASS] Idle:");
+                self.idle.print_queue_status();
+            }
+        }
+    }
+
+    /// For zoned devices. Writes are kept per zone in sector order and a zone's writes
+    /// are streamed before the next zone is started, zones in ascending order from
+    /// the head. Zones that are still open go first, so the device has to close one
+    /// only when none of them has writes queued. Reads go to the wrapped scheduler,
+    /// reads and writes take turns.
+    #[derive(Clone)]
+    pub struct ZoneAwareScheduler<S: Scheduler> {
+        pub reads: S,
+        pub writes: BTreeMap<u32, Vec<Request>>, // Zone -> writes sorted by sector
+        pub zone_sectors: u32,
+        pub max_open_zones: usize,
+        pub open_zones: VecDeque<u32>, // Written and not full, least recently written first
+        pub current_zone: Option<u32>, // Zone whose writes are being streamed
+        pub write_next: bool,          // Whose turn it is when both are queued
+        pub max_request_sectors: u32,
+    }
+
+    impl<S: Scheduler> ZoneAwareScheduler<S> {
+        pub fn new(
+            zone_sectors: u32,
+            max_open_zones: usize,
+            max_request_sectors: u32,
+            reads: S,
+        ) -> Self {
+            Self {
+                reads,
+                writes: BTreeMap::new(),
+                zone_sectors: zone_sectors.max(1),
+                max_open_zones: max_open_zones.max(1),
+                open_zones: VecDeque::new(),
+                current_zone: None,
+                write_next: false,
+                max_request_sectors,
+            }
+        }
+
+        /// The first zone with queued writes from the head on, preferring open zones.
+        fn pick_zone(&self, current_sector: u32) -> Option<u32> {
+            let head_zone = current_sector / self.zone_sectors;
+            let mut zones = self
+                .writes
+                .range(head_zone..)
+                .chain(self.writes.range(..head_zone))
+                .map(|(&zone, _)| zone);
+            let first = zones.clone().next()?;
+            Some(
+                zones
+                    .find(|zone| self.open_zones.contains(zone))
+                    .unwrap_or(first),
+            )
+        }
+
+        /// Mirrors the device's open zones: a write reaching the end of its zone fills
+        /// it, a write opening a zone past the limit closes the least recently written.
+        fn track_open_zone(&mut self, zone: u32, request: &Request) {
+            self.open_zones.retain(|&open| open != zone);
+            if request.end() >= (zone + 1) * self.zone_sectors {
+                return;
+            }
+            if self.open_zones.len() >= self.max_open_zones {
+                if let Some(closed) = self.open_zones.pop_front() {
+                    println!(
+                        "[ZONE] Open zone limit reached, zone {} gets closed.",
+                        closed
+                    );
+                }
+            }
+            self.open_zones.push_back(zone);
+        }
+
+        fn next_write(&mut self, current_sector: u32) -> Option<Request> {
+            let zone = match self.current_zone {
+                Some(zone) if self.writes.contains_key(&zone) => zone,
+                _ => {
+                    let zone = self.pick_zone(current_sector)?;
+                    println!("[ZONE] Streaming writes of zone {}.", zone);
+                    self.current_zone = Some(zone);
+                    zone
+                }
+            };
+            let queue = self.writes.get_mut(&zone)?;
+            let request = queue.remove(0);
+            if queue.is_empty() {
+                self.writes.remove(&zone);
+            }
+            println!(
+                "[ZONE] Serving write at sector {} x{} in zone {}",
+                request.sector, request.count, zone
+            );
+            self.track_open_zone(zone, &request);
+            Some(request)
+        }
+    }
+
+    impl<S: Scheduler> Scheduler for ZoneAwareScheduler<S> {
+        fn add_request(&mut self, request: Request) -> Option<MergeKind> {
+            if request.operation == IoOperation::Read {
+                return self.reads.add_request(request);
+            }
+
+            let zone = request.sector / self.zone_sectors;
+            let queue = self.writes.entry(zone).or_default();
+            let boundary = Some(self.zone_sectors);
+            if let Some(kind) = try_merge(queue, &request, self.max_request_sectors, boundary) {
+                println!(
+                    "[ZONE] {:?}-merged write for sector {} in zone {}",
+                    kind, request.sector, zone
+                );
+                queue.sort_by_key(|req| req.sector);
+                return Some(kind);
+            }
+
+            println!(
+                "[ZONE] Adding write for sector {} in zone {}",
+                request.sector, zone
+            );
+            queue.push(request);
+            queue.sort_by_key(|req| req.sector);
+            None
+        }
+
+        fn get_next_request(&mut self, current_sector: u32) -> Option<Request> {
+            let has_writes = !self.writes.is_empty();
+            let serve_write = match (self.reads.has_requests(), has_writes) {
+                (true, true) => self.write_next,
+                (false, true) => true,
+                (_, false) => false,
+            };
+            self.write_next = !serve_write;
+            if serve_write {
+                self.next_write(current_sector)
+            } else {
+                self.reads.get_next_request(current_sector)
+            }
+        }
+
+        fn has_requests(&self) -> bool {
+            self.reads.has_requests() || !self.writes.is_empty()
+        }
+
+        fn print_queue_status(&self) {
+            self.reads.print_queue_status();
+            for (zone, queue) in &self.writes {
+                println!("[ZONE] Zone {} writes:", zone);
+                for (i, request) in queue.iter().enumerate() {
+                    println!("  [{}] Sector {} x{}", i + 1, request.sector, request.count);
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::device::device::BlockDevice;
+        use crate::disk::disk::Disk;
+        use crate::golden::golden::assert_golden;
+        use proptest::prelude::*;
+        use std::collections::HashMap;
+        use std::fmt::Write as _;
+
+        /// Requests arriving over time, some adjacent so they merge while queued.
+        fn workload() -> Vec<(u32, Request)> {
+            use IoOperation::{Read, Write};
+            [
+                (0, 100, Read),
+                (0, 200, Write),
+                (0, 101, Read),
+                (0, 50, Read),
+                (0, 300, Write),
+                (10, 250, Read),
+                (10, 10, Read),
+                (10, 201, Write),
+                (40, 400, Read),
+                (40, 99, Read),
+                (40, 450, Write),
+                (90, 120, Read),
+                (90, 5, Write),
+            ]
+            .into_iter()
+            .map(|(time, sector, operation)| {
+                let request = Request {
+                    issued_at: time,
+                    ..Request::new(sector, operation)
+                };
+                (time, request)
+            })
+            .collect()
+        }
+
+        /// Serves the workload on the default disk, one request at a time. Returns the
+        /// service order with start and end times and the final time.
+        fn run(mut scheduler: impl Scheduler) -> String {
+            let mut disk = Disk::new(5, 100, 2, 4, 1).with_write_timing(2, true);
+            let mut arrivals = workload().into_iter().peekable();
+            let mut output = String::new();
+            let (mut now, mut head) = (0, 0);
+            loop {
+                while let Some((_, request)) = arrivals.next_if(|(time, _)| *time <= now) {
+                    scheduler.add_request(request);
+                }
+                if !scheduler.has_requests() {
+                    match arrivals.peek() {
+                        Some(&(time, _)) => {
+                            now = time;
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                let request = scheduler
+                    .get_next_request(head)
+                    .expect("scheduler has requests but returned none");
+                let time = disk
+                    .access(request.sector, request.count, request.operation)
+                    .unwrap();
+                let _ = writeln!(
+                    output,
+                    "{:>4} {:>4} {:?} sector {} x{}",
+                    now,
+                    now + time,
+                    request.operation,
+                    request.sector,
+                    request.count
+                );
+                now += time;
+                head = request.end() - 1;
+            }
+            let _ = writeln!(output, "done at {}", now);
+            output
+        }
+
+        #[test]
+        fn fifo_service_order() {
+            assert_golden("fifo", &run(FifoScheduler::new(8)));
+        }
+
+        #[test]
+        fn look_service_order() {
+            assert_golden("look", &run(LookScheduler::new(8)));
+        }
+
+        #[test]
+        fn flook_service_order() {
+            assert_golden("flook", &run(FlookScheduler::new(8)));
+        }
+
+        fn read(sector: u32) -> Request {
+            Request::new(sector, IoOperation::Read)
+        }
+
+        #[test]
+        fn look_turns_around_when_nothing_is_ahead() {
+            let mut look = LookScheduler::new(8);
+            for sector in [10, 60] {
+                look.add_request(read(sector));
+            }
+            assert_eq!(look.get_next_request(50).map(|r| r.sector), Some(60));
+            assert!(look.direction);
+            // Nothing past 60, the head turns IN
+            assert_eq!(look.get_next_request(60).map(|r| r.sector), Some(10));
+            assert!(!look.direction);
+
+            // Nothing below 10 either, the head turns OUT again and keeps serving
+            look.add_request(read(70));
+            assert_eq!(look.get_next_request(10).map(|r| r.sector), Some(70));
+            assert!(look.direction);
+            assert_eq!(look.get_next_request(70), None);
+            assert!(look.direction);
+        }
+
+        #[test]
+        fn flook_turns_around_when_nothing_is_ahead() {
+            let mut flook = FlookScheduler::new(8);
+            for sector in [10, 60] {
+                flook.add_request(read(sector));
+            }
+            assert_eq!(flook.get_next_request(50).map(|r| r.sector), Some(60));
+            assert_eq!(flook.get_next_request(60).map(|r| r.sector), Some(10));
+            assert!(!flook.direction);
+
+            flook.add_request(read(70));
+            assert_eq!(flook.get_next_request(10).map(|r| r.sector), Some(70));
+            assert!(flook.direction);
+            assert_eq!(flook.get_next_request(70), None);
+        }
+
+        #[test]
+        fn request_bridging_two_queued_ones_joins_them() {
+            let mut fifo = FifoScheduler::new(8);
+            for sector in [10, 20, 12] {
+                fifo.add_request(read(sector));
+            }
+            assert_eq!(fifo.add_request(read(11)), Some(MergeKind::Back));
+            let first = fifo.get_next_request(0).unwrap();
+            assert_eq!((first.sector, first.count), (10, 3));
+            assert_eq!(fifo.get_next_request(0).map(|r| r.sector), Some(20));
+            assert!(!fifo.has_requests());
+        }
+
+        #[test]
+        fn class_aware_serves_realtime_then_best_effort_then_idle() {
+            let request = |sector, io_class| Request {
+                io_class,
+                ..read(sector)
+            };
+            let mut scheduler = ClassAwareScheduler::new(|| FifoScheduler::new(8));
+            for (sector, io_class) in [
+                (10, IoClass::Idle),
+                (20, IoClass::BestEffort(4)),
+                (30, IoClass::RealTime),
+                (40, IoClass::BestEffort(0)),
+                (50, IoClass::BestEffort(4)),
+                (60, IoClass::RealTime),
+            ] {
+                scheduler.add_request(request(sector, io_class));
+            }
+            let order: Vec<u32> = std::iter::from_fn(|| scheduler.get_next_request(0))
+                .map(|r| r.sector)
+                .collect();
+            assert_eq!(order, [30, 60, 40, 20, 50, 10]);
+        }
+
+        #[test]
+        fn class_aware_gives_best_effort_levels_their_share() {
+            let mut scheduler = ClassAwareScheduler::new(|| FifoScheduler::new(8));
+            let sectors = (0..10)
+                .map(|n| (n * 10, 0))
+                .chain([(500, 6), (510, 6), (520, 6)]);
+            for (sector, level) in sectors {
+                scheduler.add_request(Request {
+                    io_class: IoClass::BestEffort(level),
+                    ..read(sector)
+                });
+            }
+            // Level 0 gets 8 dispatches per turn, level 6 gets 2
+            let levels: Vec<IoClass> = std::iter::from_fn(|| scheduler.get_next_request(0))
+                .map(|r| r.io_class)
+                .collect();
+            let expected = [0, 0, 0, 0, 0, 0, 0, 0, 6, 6, 0, 0, 6].map(IoClass::BestEffort);
+            assert_eq!(levels, expected);
+        }
+
+        #[test]
+        fn zone_aware_merges_stay_in_their_zone() {
+            let write = |sector, count| Request {
+                count,
+                ..Request::new(sector, IoOperation::Write)
+            };
+            let mut zoned = ZoneAwareScheduler::new(50, 4, 8, FifoScheduler::new(8));
+            zoned.add_request(write(46, 2));
+            // 48..52 would grow the write at 46 into zone 1
+            assert_eq!(zoned.add_request(write(48, 4)), None);
+            assert_eq!(zoned.add_request(write(44, 2)), Some(MergeKind::Front));
+            let first = zoned.get_next_request(0).unwrap();
+            assert_eq!((first.sector, first.count), (44, 4));
+        }
+
+        #[test]
+        fn zone_aware_prefers_open_zones() {
+            let write = |sector| Request::new(sector, IoOperation::Write);
+            let mut zoned = ZoneAwareScheduler::new(50, 2, 8, FifoScheduler::new(8));
+            for sector in [0, 50] {
+                zoned.add_request(write(sector));
+            }
+            zoned.get_next_request(0);
+            zoned.get_next_request(0);
+            assert_eq!(zoned.open_zones, [0, 1]);
+
+            // Zone 4 is next from the head, but zone 1 is open and goes first
+            for sector in [200, 51] {
+                zoned.add_request(write(sector));
+            }
+            assert_eq!(zoned.get_next_request(150).map(|r| r.sector), Some(51));
+            assert_eq!(zoned.get_next_request(150).map(|r| r.sector), Some(200));
+            assert_eq!(zoned.open_zones, [1, 4]);
+
+            // Filling a zone to its end closes it
+            zoned.add_request(write(249));
+            zoned.get_next_request(200);
+            assert_eq!(zoned.open_zones, [1]);
+        }
+
+        /// One step of a random stream: queue a request, or serve one with the head
+        /// on the last sector served.
+        #[derive(Debug, Clone)]
+        enum Step {
+            Add(Request),
+            Serve,
+        }
+
+        fn request() -> impl Strategy<Value = Request> {
+            (0..120u32, 1..4u32, any::<bool>(), 0..10u8).prop_map(
+                |(sector, count, write, class)| Request {
+                    count,
+                    io_class: match class {
+                        0 => IoClass::RealTime,
+                        9 => IoClass::Idle,
+                        level => IoClass::BestEffort(level - 1),
+                    },
+                    ..Request::new(
+                        sector,
+                        if write {
+                            IoOperation::Write
+                        } else {
+                            IoOperation::Read
+                        },
+                    )
+                },
+            )
+        }
+
+        fn steps() -> impl Strategy<Value = Vec<Step>> {
+            prop::collection::vec(
+                prop_oneof![2 => request().prop_map(Step::Add), 1 => Just(Step::Serve)],
+                0..60,
+            )
+        }
+
+        /// Feeds the steps to the scheduler, then drains it. `check` sees the scheduler
+        /// before and after each dispatch, the head and the served request. Fails unless
+        /// every added sector is served exactly once, merged or not.
+        fn serve_all<S: Scheduler + Clone>(
+            mut scheduler: S,
+            steps: &[Step],
+            mut check: impl FnMut(&S, &S, u32, &Request) -> Result<(), TestCaseError>,
+        ) -> Result<(), TestCaseError> {
+            let mut pending: HashMap<(u32, bool), i32> = HashMap::new();
+            let mut head = 0;
+            let drain = std::iter::repeat(None);
+            for step in steps.iter().map(Some).chain(drain) {
+                match step {
+                    Some(Step::Add(request)) => {
+                        for sector in request.sector..request.end() {
+                            let key = (sector, request.operation == IoOperation::Write);
+                            *pending.entry(key).or_default() += 1;
+                        }
+                        scheduler.add_request(request.clone());
+                    }
+                    None if !scheduler.has_requests() => break,
+                    _ => {
+                        let before = scheduler.clone();
+                        let served = scheduler.get_next_request(head);
+                        prop_assert_eq!(served.is_some(), before.has_requests());
+                        let Some(served) = served else {
+                            continue;
+                        };
+                        check(&before, &scheduler, head, &served)?;
+                        for sector in served.sector..served.end() {
+                            let key = (sector, served.operation == IoOperation::Write);
+                            let count = pending.entry(key).or_default();
+                            *count -= 1;
+                            prop_assert!(
+                                *count >= 0,
+                                "sector {} served more often than added",
+                                sector
+                            );
+                        }
+                        head = served.end() - 1;
+                    }
+                }
+            }
+            prop_assert!(
+                pending.values().all(|&count| count == 0),
+                "drained with sectors never served"
+            );
+            Ok(())
+        }
+
+        fn any_order<S>(_: &S, _: &S, _: u32, _: &Request) -> Result<(), TestCaseError> {
+            Ok(())
+        }
+
+        proptest! {
+            #[test]
+            fn fifo_serves_every_request_once(steps in steps()) {
+                serve_all(FifoScheduler::new(8), &steps, any_order)?;
+            }
+
+            #[test]
+            fn look_keeps_direction_while_requests_remain(steps in steps()) {
+                serve_all(LookScheduler::new(8), &steps, |before, after, head, served| {
+                    let ahead = |request: &Request| {
+                        if before.direction {
+                            request.sector >= head
+                        } else {
+                            request.sector <= head
+                        }
+                    };
+                    if before.queue.iter().any(ahead) {
+                        prop_assert!(ahead(served), "reversed at {} to serve {:?}", head, served);
+                        prop_assert_eq!(after.direction, before.direction);
+                    }
+                    Ok(())
+                })?;
+            }
+
+            #[test]
+            fn flook_drains_active_queue_first(steps in steps()) {
+                serve_all(FlookScheduler::new(8), &steps, |before, after, _, served| {
+                    if before.active_queue.is_empty() {
+                        prop_assert!(before.waiting_queue.contains(served));
+                    } else {
+                        prop_assert!(before.active_queue.contains(served));
+                        prop_assert_eq!(&after.waiting_queue, &before.waiting_queue);
+                    }
+                    Ok(())
+                })?;
+            }
+
+            #[test]
+            fn class_aware_serves_every_request_once(steps in steps()) {
+                let scheduler = ClassAwareScheduler::new(|| LookScheduler::new(8));
+                serve_all(scheduler, &steps, any_order)?;
+            }
+
+            #[test]
+            fn zone_aware_serves_every_request_once(steps in steps()) {
+                let scheduler = ZoneAwareScheduler::new(50, 2, 8, FlookScheduler::new(8));
+                serve_all(scheduler, &steps, |_, after, _, _| {
+                    prop_assert!(after.open_zones.len() <= after.max_open_zones);
+                    Ok(())
+                })?;
+            }
+        }
+    }
 }
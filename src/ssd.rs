@@ -0,0 +1,339 @@
+pub mod ssd {
+    use crate::device::device::{BlockDevice, DeviceError};
+    use crate::scheduler::scheduler::IoOperation;
+    use std::collections::{HashMap, VecDeque};
+
+    /// Physical location of a flash page.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PageAddress {
+        pub die: usize,
+        pub block: usize,
+        pub page: usize,
+    }
+
+    #[derive(Debug)]
+    pub struct Block {
+        pub pages: Vec<Option<u32>>, // Logical page stored in each written page, None once invalid
+        pub next_page: usize,        // Pages are programmed in order
+        pub erase_count: u32,
+    }
+
+    impl Block {
+        fn valid_pages(&self) -> usize {
+            self.pages.iter().filter(|page| page.is_some()).count()
+        }
+
+        fn is_full(&self) -> bool {
+            self.next_page == self.pages.len()
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Die {
+        pub blocks: Vec<Block>,
+        pub free_blocks: VecDeque<usize>,
+        pub active_block: Option<usize>, // Block new pages are programmed into
+    }
+
+    #[derive(Debug, Default)]
+    pub struct SsdStats {
+        pub host_pages_written: u32,
+        pub flash_pages_written: u32, // Host writes plus pages moved by garbage collection
+        pub pages_read: u32,
+        pub erases: u32,
+        pub gc_runs: u32,
+        pub gc_pages_moved: u32,
+    }
+
+    /// Flash drive with one sector per page. Logical pages are striped over the dies,
+    /// which work in parallel, dies on the same channel share its bus. A page-mapping
+    /// FTL writes out of place and garbage collects the block with the fewest valid
+    /// pages when a die runs low on free blocks.
+    pub struct Ssd {
+        pub channels: u32,
+        pub pages_per_block: u32,
+        pub read_time: u32,             // Reading a page into the die register
+        pub program_time: u32,          // Programming a page
+        pub erase_time: u32,            // Erasing a block
+        pub transfer_time: u32,         // Moving a page over the channel bus
+        pub gc_threshold: usize,        // Free blocks per die garbage collection keeps
+        pub overprovision_percent: u32, // Share of the flash hidden from the host
+        pub dies: Vec<Die>,
+        pub mapping: HashMap<u32, PageAddress>, // Logical page -> physical page
+        pub stats: SsdStats,
+    }
+
+    impl Ssd {
+        pub fn new(
+            channels: u32,
+            dies_per_channel: u32,
+            blocks_per_die: u32,
+            pages_per_block: u32,
+        ) -> Self {
+            let dies = (0..channels * dies_per_channel)
+                .map(|_| Die {
+                    blocks: (0..blocks_per_die)
+                        .map(|_| Block {
+                            pages: vec![None; pages_per_block as usize],
+                            next_page: 0,
+                            erase_count: 0,
+                        })
+                        .collect(),
+                    free_blocks: (0..blocks_per_die as usize).collect(),
+                    active_block: None,
+                })
+                .collect();
+            Self {
+                channels,
+                pages_per_block,
+                read_time: 1,
+                program_time: 2,
+                erase_time: 10,
+                transfer_time: 1,
+                gc_threshold: 2,
+                overprovision_percent: 25,
+                dies,
+                mapping: HashMap::new(),
+                stats: SsdStats::default(),
+            }
+        }
+
+        pub fn with_timing(
+            mut self,
+            read_time: u32,
+            program_time: u32,
+            erase_time: u32,
+            transfer_time: u32,
+        ) -> Self {
+            self.read_time = read_time;
+            self.program_time = program_time;
+            self.erase_time = erase_time;
+            self.transfer_time = transfer_time;
+            self
+        }
+
+        pub fn write_amplification(&self) -> f64 {
+            if self.stats.host_pages_written == 0 {
+                1.0
+            } else {
+                self.stats.flash_pages_written as f64 / self.stats.host_pages_written as f64
+            }
+        }
+
+        fn die_of(&self, logical_page: u32) -> usize {
+            logical_page as usize % self.dies.len()
+        }
+
+        fn channel_of(&self, die: usize) -> usize {
+            die % self.channels as usize
+        }
+
+        /// Next free page of a die, opening a new block when the active one is full.
+        fn allocate(&mut self, die: usize) -> Option<PageAddress> {
+            let state = &mut self.dies[die];
+            let block = match state.active_block {
+                Some(block) if !state.blocks[block].is_full() => block,
+                _ => {
+                    let block = state.free_blocks.pop_front()?;
+                    state.active_block = Some(block);
+                    block
+                }
+            };
+            let page = state.blocks[block].next_page;
+            state.blocks[block].next_page += 1;
+            Some(PageAddress { die, block, page })
+        }
+
+        /// Erased pages a die can still program, in its active block and free blocks.
+        fn free_pages(&self, die: usize) -> usize {
+            let state = &self.dies[die];
+            let active = state.active_block.map_or(0, |block| {
+                self.pages_per_block as usize - state.blocks[block].next_page
+            });
+            active + state.free_blocks.len() * self.pages_per_block as usize
+        }
+
+        /// Writes a logical page out of place and invalidates its old copy.
+        fn program(&mut self, logical_page: u32, die: usize) -> Result<(), DeviceError> {
+            let Some(address) = self.allocate(die) else {
+                println!("[SSD] Die {} is out of free pages.", die);
+                return Err(DeviceError::NoFreePages { die });
+            };
+            if let Some(old) = self.mapping.insert(logical_page, address) {
+                self.dies[old.die].blocks[old.block].pages[old.page] = None;
+            }
+            self.dies[die].blocks[address.block].pages[address.page] = Some(logical_page);
+            self.stats.flash_pages_written += 1;
+            Ok(())
+        }
+
+        /// Frees blocks on a die until it has `gc_threshold` of them again. Returns the
+        /// time the die spent on it. A victim is only picked if all of its valid pages
+        /// fit elsewhere on the die, so a block is always moved and erased whole.
+        fn collect_garbage(&mut self, die: usize) -> Result<u32, DeviceError> {
+            let mut time = 0;
+            while self.dies[die].free_blocks.len() < self.gc_threshold {
+                let state = &self.dies[die];
+                let victim = (0..state.blocks.len())
+                    .filter(|&block| {
+                        Some(block) != state.active_block
+                            && state.blocks[block].is_full()
+                            && state.blocks[block].valid_pages() < self.pages_per_block as usize
+                    })
+                    .min_by_key(|&block| state.blocks[block].valid_pages());
+                let Some(victim) = victim else {
+                    break;
+                };
+                if state.blocks[victim].valid_pages() > self.free_pages(die) {
+                    // The next write that finds no free page fails instead
+                    println!("[SSD] GC on die {}: no room to move block {}.", die, victim);
+                    break;
+                }
+
+                let valid: Vec<u32> = state.blocks[victim]
+                    .pages
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .collect();
+                println!(
+                    "[SSD] GC on die {}: block {} has {} valid pages, moving them.",
+                    die,
+                    victim,
+                    valid.len()
+                );
+                self.stats.gc_runs += 1;
+                for logical_page in valid {
+                    self.program(logical_page, die)?;
+                    self.stats.gc_pages_moved += 1;
+                    time += self.read_time + self.program_time;
+                }
+
+                let block = &mut self.dies[die].blocks[victim];
+                block.pages.iter_mut().for_each(|page| *page = None);
+                block.next_page = 0;
+                block.erase_count += 1;
+                self.dies[die].free_blocks.push_back(victim);
+                self.stats.erases += 1;
+                time += self.erase_time;
+            }
+            Ok(time)
+        }
+    }
+
+    impl BlockDevice for Ssd {
+        fn name(&self) -> &'static str {
+            "SSD"
+        }
+
+        fn capacity(&self) -> u32 {
+            let blocks = self.dies.first().map_or(0, |die| die.blocks.len()) as u32;
+            let pages = self.dies.len() as u32 * blocks * self.pages_per_block;
+            pages * (100 - self.overprovision_percent) / 100
+        }
+
+        /// No seek and no rotation. Each die works through its own pages, the slowest
+        /// die or the busiest channel bus decides the time.
+        fn access(
+            &mut self,
+            sector: u32,
+            count: u32,
+            operation: IoOperation,
+        ) -> Result<u32, DeviceError> {
+            let mut die_time = vec![0; self.dies.len()];
+            let mut bus_time = vec![0; self.channels as usize];
+            for logical_page in sector..sector + count {
+                let die = self.die_of(logical_page);
+                bus_time[self.channel_of(die)] += self.transfer_time;
+                match operation {
+                    IoOperation::Read => {
+                        die_time[die] += self.read_time;
+                        self.stats.pages_read += 1;
+                    }
+                    IoOperation::Write => {
+                        self.program(logical_page, die)?;
+                        self.stats.host_pages_written += 1;
+                        die_time[die] += self.program_time;
+                        die_time[die] += self.collect_garbage(die)?;
+                    }
+                    IoOperation::ZoneReset => return Err(DeviceError::NoZones),
+                }
+            }
+
+            let time = die_time
+                .iter()
+                .chain(bus_time.iter())
+                .copied()
+                .max()
+                .unwrap_or(0);
+            println!(
+                "[SSD] {:?} of {} page(s) from page {}. Busiest die: {}ms, busiest channel: {}ms.",
+                operation,
+                count,
+                sector,
+                die_time.iter().max().unwrap_or(&0),
+                bus_time.iter().max().unwrap_or(&0)
+            );
+            Ok(time)
+        }
+
+        fn print_report(&self) {
+            let max_erases = self
+                .dies
+                .iter()
+                .flat_map(|die| die.blocks.iter().map(|block| block.erase_count))
+                .max()
+                .unwrap_or(0);
+            println!(
+                "[REPORT] SSD: {} host pages written, {} flash pages written, write amplification {:.2}",
+                self.stats.host_pages_written,
+                self.stats.flash_pages_written,
+                self.write_amplification()
+            );
+            println!(
+                "[REPORT] SSD: {} pages read, {} GC runs moved {} pages, {} erases (max {} per block)",
+                self.stats.pages_read,
+                self.stats.gc_runs,
+                self.stats.gc_pages_moved,
+                self.stats.erases,
+                max_erases
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn write_without_free_page_fails() {
+            // One die of two blocks with two pages each, nothing invalid to collect
+            let mut ssd = Ssd::new(1, 1, 2, 2);
+            for page in 0..4 {
+                assert!(ssd.access(page, 1, IoOperation::Write).is_ok());
+            }
+            assert_eq!(
+                ssd.access(4, 1, IoOperation::Write),
+                Err(DeviceError::NoFreePages { die: 0 })
+            );
+            assert_eq!(ssd.stats.host_pages_written, 4);
+        }
+
+        #[test]
+        fn garbage_collection_moves_whole_blocks() {
+            let mut ssd = Ssd::new(1, 1, 4, 4);
+            for page in [0, 1, 2, 3, 0, 1, 2, 4, 5, 0, 1, 6, 7, 2] {
+                ssd.access(page, 1, IoOperation::Write).unwrap();
+            }
+            assert!(ssd.stats.erases > 0);
+            // Every mapped page points at a programmed page holding it
+            for (&logical_page, address) in &ssd.mapping {
+                let block = &ssd.dies[address.die].blocks[address.block];
+                assert!(address.page < block.next_page);
+                assert_eq!(block.pages[address.page], Some(logical_page));
+            }
+            let valid: usize = ssd.dies[0].blocks.iter().map(Block::valid_pages).sum();
+            assert_eq!(valid, ssd.mapping.len());
+        }
+    }
+}
@@ -0,0 +1,140 @@
+pub mod stats {
+    use crate::scheduler::scheduler::{IoClass, IoOperation, MergeKind};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Default)]
+    pub struct LatencyStats {
+        pub count: u32,
+        pub total: u32,
+        pub max: u32,
+    }
+
+    impl LatencyStats {
+        pub fn record(&mut self, latency: u32) {
+            self.count += 1;
+            self.total += latency;
+            self.max = self.max.max(latency);
+        }
+
+        pub fn average(&self) -> f64 {
+            if self.count == 0 {
+                0.0
+            } else {
+                self.total as f64 / self.count as f64
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct Stats {
+        pub requests: u32,     // Requests issued by processes
+        pub cache_hits: u32,   // Requests served from the cache
+        pub queued: u32,       // Requests that reached the scheduler queue
+        pub front_merges: u32, // Requests merged in front of a queued request
+        pub back_merges: u32,  // Requests merged at the end of a queued request
+        pub dispatched: u32,   // Requests sent to the disk after merging
+        pub sectors_transferred: u32,
+        pub disk_time: u32,
+        pub disk_reads: u32,             // Sectors read from the disk
+        pub disk_writes: u32,            // Sectors written to the disk
+        pub writebacks: u32,             // Dirty buffers written back on eviction
+        pub flushed: u32,                // Dirty buffers written back by the flusher
+        pub write_through: u32,          // Writes sent to the disk by the write-through policy
+        pub write_around: u32,           // Writes sent to the disk by the write-around policy
+        pub io_errors: u32,              // Requests a device failed
+        pub zone_resets: u32,            // Zones reset by processes
+        pub read_latency: LatencyStats,  // Issue to completion, cache hits included, failures not
+        pub write_latency: LatencyStats, // Issue to completion, cache hits included, failures not
+        pub cpu_busy: u32,               // CPU time used by processes
+        pub io_wait: u32,                // CPU idle while a process waited for the disk
+        pub cpu_idle: u32,               // CPU idle with no process waiting for the disk
+        pub class_latency: BTreeMap<IoClass, LatencyStats>,
+    }
+
+    impl Stats {
+        /// Counts a request queued for the volume and how its pieces were merged
+        /// in the member queues.
+        pub fn record_queued(&mut self, merges: Vec<Option<MergeKind>>) {
+            self.queued += 1;
+            for merge in merges {
+                match merge {
+                    Some(MergeKind::Front) => self.front_merges += 1,
+                    Some(MergeKind::Back) => self.back_merges += 1,
+                    None => {}
+                }
+            }
+        }
+
+        pub fn record_latency(&mut self, operation: IoOperation, io_class: IoClass, latency: u32) {
+            match operation {
+                IoOperation::Read => self.read_latency.record(latency),
+                IoOperation::Write => self.write_latency.record(latency),
+                // Served at issue time, they never wait for the disk
+                IoOperation::ZoneReset => return,
+            }
+            self.class_latency
+                .entry(io_class)
+                .or_default()
+                .record(latency);
+        }
+
+        pub fn print_report(&self, current_time: u32, cache_policy: &str) {
+            println!("\n[REPORT] Simulated time: {}ms", current_time);
+            println!("[REPORT] Cache policy: {}", cache_policy);
+            println!(
+                "[REPORT] Requests: {}, cache hits: {}, queued for disk: {}",
+                self.requests, self.cache_hits, self.queued
+            );
+            println!(
+                "[REPORT] Merges: {} front, {} back. Disk dispatches: {} ({} sectors, {}ms)",
+                self.front_merges,
+                self.back_merges,
+                self.dispatched,
+                self.sectors_transferred,
+                self.disk_time
+            );
+            println!(
+                "[REPORT] Disk traffic: {} sectors read, {} sectors written",
+                self.disk_reads, self.disk_writes
+            );
+            println!(
+                "[REPORT] Disk writes by cause: {} write-back on eviction, {} flushed, {} write-through, {} write-around",
+                self.writebacks, self.flushed, self.write_through, self.write_around
+            );
+            if self.io_errors > 0 {
+                println!("[REPORT] I/O errors: {} requests failed", self.io_errors);
+            }
+            if self.zone_resets > 0 {
+                println!("[REPORT] Zone resets: {}", self.zone_resets);
+            }
+            let cpu_total = self.cpu_busy + self.io_wait + self.cpu_idle;
+            let utilization = if cpu_total == 0 {
+                0.0
+            } else {
+                100.0 * self.cpu_busy as f64 / cpu_total as f64
+            };
+            println!(
+                "[REPORT] CPU: {}ms busy ({:.1}% utilization), {}ms I/O wait, {}ms idle",
+                self.cpu_busy, utilization, self.io_wait, self.cpu_idle
+            );
+            for (name, latency) in [("Read", &self.read_latency), ("Write", &self.write_latency)] {
+                println!(
+                    "[REPORT] {} latency: {} completions, avg {:.1}ms, max {}ms",
+                    name,
+                    latency.count,
+                    latency.average(),
+                    latency.max
+                );
+            }
+            for (io_class, latency) in &self.class_latency {
+                println!(
+                    "[REPORT] I/O class {} latency: {} completions, avg {:.1}ms, max {}ms",
+                    io_class,
+                    latency.count,
+                    latency.average(),
+                    latency.max
+                );
+            }
+        }
+    }
+}
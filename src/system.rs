@@ -1,121 +1,853 @@
 pub mod system {
-    use crate::process::process::ProcessManager;
-    use crate::scheduler::scheduler::Scheduler;
-    use crate::{Disk, LfuCache};
+    use crate::cache::cache::{Buffer, CacheOutcome, CachePolicy, WritePolicy};
+    use crate::cpu::cpu::{CpuScheduler, RoundRobin};
+    use crate::device::device::DeviceError;
+    use crate::process::process::{ProcessError, ProcessManager, ProcessState};
+    use crate::readahead::readahead::ReadAhead;
+    use crate::scheduler::scheduler::{IoClass, IoOperation, Request, RequestOrigin, Scheduler};
+    use crate::stats::stats::Stats;
+    use crate::trace::trace::TraceRecorder;
+    use crate::volume::volume::Volume;
 
-    pub struct System<S: Scheduler> {
+    pub const DEFAULT_FLUSH_INTERVAL: u32 = 50;
+    pub const DEFAULT_DIRTY_EXPIRE: u32 = 100;
+
+    fn report(result: Result<(), ProcessError>) {
+        if let Err(err) = result {
+            println!("[ERROR] {}", err);
+        }
+    }
+
+    pub struct System<S: Scheduler, C: CachePolicy> {
         pub process_manager: ProcessManager,
-        pub scheduler: S,
-        pub quantum_time: u32,
         pub current_time: u32,
-        pub cache: LfuCache,
-        pub disk: Disk,
+        pub cache: C,
+        pub volume: Volume<S>,
+        pub stats: Stats,
+        pub flush_interval: u32, // How often the flusher wakes up
+        pub dirty_expire: u32,   // Age after which the flusher writes a dirty buffer back
+        pub last_flush: u32,
+        pub write_policy: WritePolicy,
+        pub readahead: Option<ReadAhead>,
+        pub recorder: Option<TraceRecorder>,
     }
 
-    impl<S: Scheduler> System<S> {
-        pub fn new(scheduler: S, cache: LfuCache, disk: Disk, quantum_time: u32) -> Self {
+    impl<S: Scheduler, C: CachePolicy> System<S, C> {
+        pub fn new(cache: C, volume: Volume<S>) -> Self {
             Self {
-                process_manager: ProcessManager::new(),
-                scheduler,
-                quantum_time,
+                process_manager: ProcessManager::new(Box::new(RoundRobin::new())),
                 current_time: 0,
                 cache,
-                disk,
+                volume,
+                stats: Stats::default(),
+                flush_interval: DEFAULT_FLUSH_INTERVAL,
+                dirty_expire: DEFAULT_DIRTY_EXPIRE,
+                last_flush: 0,
+                write_policy: WritePolicy::WriteBack,
+                readahead: None,
+                recorder: None,
             }
         }
 
+        /// Must be set before processes are added.
+        pub fn with_cpu_scheduler(mut self, cpu_scheduler: Box<dyn CpuScheduler>) -> Self {
+            self.process_manager = ProcessManager::new(cpu_scheduler);
+            self
+        }
+
+        pub fn with_readahead(mut self, min_window: u32, max_window: u32) -> Self {
+            self.readahead = Some(ReadAhead::new(min_window, max_window));
+            self
+        }
+
+        /// Records the requests of the run to a trace file at `path`.
+        pub fn with_recorder(mut self, path: &str) -> Self {
+            self.recorder = Some(TraceRecorder::new(path));
+            self
+        }
+
+        pub fn with_write_policy(mut self, write_policy: WritePolicy) -> Self {
+            self.write_policy = write_policy;
+            self
+        }
+
+        pub fn with_flusher(mut self, flush_interval: u32, dirty_expire: u32) -> Self {
+            self.flush_interval = flush_interval;
+            self.dirty_expire = dirty_expire;
+            self
+        }
+
         pub fn run(&mut self) {
-            while !self.process_manager.run_q.is_empty() || !self.process_manager.sleep_q.is_empty()
-            {
-                if let Some(mut process) = self.process_manager.run_q.pop_front() {
+            while self.process_manager.has_live_processes() {
+                for process_id in self.process_manager.wake_sleepers(self.current_time) {
                     println!(
-                        "[Time {}] Running process {}.",
-                        self.current_time, process.id
+                        "[Time {}] Process {} woke up.",
+                        self.current_time, process_id
                     );
+                }
+                self.run_flusher();
+                self.start_requests();
+                if let Some(process_id) = self.process_manager.schedule() {
+                    self.process_manager.cpu_scheduler.print_queue_status();
+                    self.run_process(process_id);
+                    continue;
+                }
+
+                // Nothing can run until the disk finishes or a sleeper wakes up
+                let next_event = self
+                    .volume
+                    .next_completion()
+                    .into_iter()
+                    .chain(self.process_manager.next_wake_time())
+                    .chain(self.next_flush())
+                    .min();
+                let Some(next_event) = next_event else {
+                    println!(
+                        "[ERROR] {} process(es) blocked on I/O with no request queued.",
+                        self.process_manager.blocked_processes()
+                    );
+                    break;
+                };
+                let idle = next_event.saturating_sub(self.current_time);
+                if self.process_manager.blocked_processes() > 0 {
+                    self.stats.io_wait += idle;
+                } else {
+                    self.stats.cpu_idle += idle;
+                }
+                println!(
+                    "[Time {}] SCHEDULER: CPU idle until {}.",
+                    self.current_time, next_event
+                );
+                self.advance_to(next_event);
+            }
 
-                    // Add all process requests to the scheduler queue
-                    for request in &process.requests {
-                        self.scheduler.add_request(request.clone());
+            println!("\n[Time {}] All processes completed.", self.current_time);
+            self.sync();
+            self.stats
+                .print_report(self.current_time, self.cache.name());
+            println!(
+                "[REPORT] CPU scheduler: {}",
+                self.process_manager.cpu_scheduler.name()
+            );
+            for process in self.process_manager.processes.values() {
+                println!(
+                    "[REPORT] Process {}: {}ms of CPU time",
+                    process.id, process.cpu_time
+                );
+            }
+            let segmented = self.cache.segment_sizes().len() > 1;
+            self.cache.stats().print_report(segmented);
+            self.volume.idle_until(self.current_time);
+            self.volume.print_report();
+            if let Some(recorder) = &self.recorder {
+                match recorder.save() {
+                    Ok(()) => println!(
+                        "[REPORT] Recorded {} requests to {}",
+                        recorder.requests.len(),
+                        recorder.path
+                    ),
+                    Err(err) => println!("[ERROR] Cannot write trace {}: {}", recorder.path, err),
+                }
+            }
+            let buffers: Vec<_> = self
+                .volume
+                .members
+                .iter()
+                .filter_map(|member| member.device.track_buffer())
+                .collect();
+            if !buffers.is_empty() {
+                println!(
+                    "[REPORT] Hits by level: host cache {}, drive track buffer {}, media {}",
+                    self.stats.cache_hits,
+                    buffers.iter().map(|buffer| buffer.stats.hits).sum::<u32>(),
+                    buffers
+                        .iter()
+                        .map(|buffer| buffer.stats.misses)
+                        .sum::<u32>()
+                );
+            }
+            if let Some(readahead) = &self.readahead {
+                readahead.print_report();
+            }
+        }
+
+        /// Runs a process until its quantum expires, it blocks on I/O, starts thinking
+        /// or runs out of requests.
+        fn run_process(&mut self, process_id: u32) {
+            if let Some(process) = self.process_manager.get_mut(process_id) {
+                println!(
+                    "[Time {}] Running process {} for up to {}ms.",
+                    self.current_time, process_id, process.time_slice
+                );
+            }
+            loop {
+                let Some(process) = self.process_manager.get_mut(process_id) else {
+                    return;
+                };
+                if !process.has_requests() {
+                    println!(
+                        "[Time {}] Process {} completed.",
+                        self.current_time, process_id
+                    );
+                    report(
+                        self.process_manager
+                            .transition(process_id, ProcessState::Terminated),
+                    );
+                    return;
+                }
+                if process.time_remaining == 0 {
+                    println!(
+                        "[Time {}] Process {} used up its time slice, preempted.",
+                        self.current_time, process_id
+                    );
+                    report(
+                        self.process_manager
+                            .transition(process_id, ProcessState::Ready),
+                    );
+                    return;
+                }
+                let think_time = process.take_think_time();
+                if think_time > 0 {
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.record_think(process_id, think_time);
+                    }
+                    println!(
+                        "[Time {}] Process {} thinking for {}ms.",
+                        self.current_time, process_id, think_time
+                    );
+                    let wake_at = self.current_time + think_time;
+                    report(self.process_manager.sleep(process_id, wake_at));
+                    return;
+                }
+                let burst = process.cpu_burst().min(process.time_remaining);
+                if burst > 0 {
+                    process.compute(burst);
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.record_cpu(process_id, burst);
                     }
+                    println!(
+                        "[Time {}] Process {} computing for {}ms.",
+                        self.current_time, process_id, burst
+                    );
+                    self.stats.cpu_busy += burst;
+                    self.advance_to(self.current_time + burst);
+                    continue;
+                }
+                let io_class = process.io_class();
+                let Some(mut request) = process.next_request() else {
+                    return;
+                };
+
+                println!(
+                    "[Time {}] Process {} processing request for sector {} ({:?}).",
+                    self.current_time, process_id, request.sector, request.operation
+                );
+                request.issued_at = self.current_time;
+                request.origin = RequestOrigin::Process(process_id);
+                request.io_class = io_class;
+                let completed = self.issue_request(request.clone());
+                self.cache.record_occupancy(self.current_time);
+                if !completed {
+                    println!(
+                        "[Time {}] Process {} blocked on I/O.",
+                        self.current_time, process_id
+                    );
+                    report(
+                        self.process_manager
+                            .transition(process_id, ProcessState::BlockedOnIo(request)),
+                    );
+                    self.start_requests();
+                    return;
+                }
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.record_completion(process_id, self.current_time);
+                }
+                self.start_requests();
+            }
+        }
 
-                    while process.time_remaining > 0 && process.has_requests() {
-                        if let Some(request) = process.next_request() {
-                            println!(
-                                "[Time {}] Process {} processing request for sector {} ({:?}).",
-                                self.current_time, process.id, request.sector, request.operation
-                            );
-
-                            if self.cache.left.iter().any(|b| b.sector == request.sector)
-                                || self.cache.middle.iter().any(|b| b.sector == request.sector)
-                                || self.cache.right.iter().any(|b| b.sector == request.sector)
-                            {
-                                println!(
-                                    "[Time {}] CACHE: Sector {} found in cache.",
-                                    self.current_time, request.sector
-                                );
-                                self.current_time += 1;
-                            } else {
-                                println!(
-                                    "[Time {}] CACHE: Sector {} not found in cache. Accessing disk.",
-                                    self.current_time, request.sector
-                                );
-                                let (track, sector_offset) =
-                                    self.disk.get_track_sector(request.sector);
-                                let disk_time = self.disk.simulate_access(track, sector_offset);
-                                println!(
-                                    "[Time {}] DRIVER: Accessing track {}, sector {}. Time: {}ms.",
-                                    self.current_time, track, sector_offset, disk_time
-                                );
-                                self.current_time += disk_time;
-                                self.cache.access(request.sector);
-                            }
-
-                            process.time_remaining -= 1;
+        /// Returns true if the request completed without waiting for the disk.
+        fn issue_request(&mut self, request: Request) -> bool {
+            self.stats.requests += 1;
+            let in_range = self.volume.check_range(&request);
+            // Every access goes through the policy, so hits update its frequencies
+            let outcome = if in_range.is_ok() && request.operation != IoOperation::ZoneReset {
+                self.cache.advance_time(self.current_time);
+                self.cache.access(request.sector)
+            } else {
+                CacheOutcome::Miss
+            };
+            let issuer = match request.origin {
+                RequestOrigin::Process(process_id) => {
+                    self.process_manager.processes.get(&process_id)
+                }
+                _ => None,
+            };
+            if let (Some(recorder), Some(process)) = (self.recorder.as_mut(), issuer) {
+                let head_sector = self.volume.head_sector(request.sector);
+                recorder.record_issue(&request, process, outcome, head_sector);
+            }
+            if let Err(err) = in_range {
+                // Nothing past the end of the volume reaches the cache or the disks
+                self.fail_request(&request, err);
+                return true;
+            }
+            if request.operation == IoOperation::ZoneReset {
+                self.reset_zone(&request);
+                return true;
+            }
+            if outcome != CacheOutcome::Miss {
+                self.stats.cache_hits += 1;
+            }
+
+            if request.operation == IoOperation::Write {
+                return self.issue_write(request, outcome);
+            }
+
+            let prefetched = self.readahead.as_mut().is_some_and(|readahead| {
+                readahead.on_hit(request.sector, outcome != CacheOutcome::Miss)
+            });
+            let (origin, io_class, sector) = (request.origin, request.io_class, request.sector);
+            if let CacheOutcome::Hit { segment } = outcome {
+                println!(
+                    "[Time {}] CACHE: Sector {} found in cache ({} segment).",
+                    self.current_time, request.sector, segment
+                );
+                self.copy_buffer(origin);
+                self.stats
+                    .record_latency(request.operation, request.io_class, 1);
+            } else if prefetched {
+                println!(
+                    "[Time {}] CACHE: Sector {} is being prefetched. Waiting for it.",
+                    self.current_time, request.sector
+                );
+            } else {
+                println!(
+                    "[Time {}] CACHE: Sector {} not found in cache. Queueing for disk.",
+                    self.current_time, request.sector
+                );
+                self.queue(request);
+            }
+
+            if let RequestOrigin::Process(process_id) = origin {
+                self.read_ahead(process_id, io_class, sector);
+            }
+            outcome != CacheOutcome::Miss
+        }
+
+        /// Queues prefetch requests for the uncached sectors ahead of a sequential reader,
+        /// in the I/O class of the reader.
+        fn read_ahead(&mut self, process_id: u32, io_class: IoClass, sector: u32) {
+            let Some(readahead) = self.readahead.as_mut() else {
+                return;
+            };
+            let Some((start, count)) = readahead.on_read(process_id, sector) else {
+                return;
+            };
+            let count = count.min(self.volume.capacity().saturating_sub(start));
+
+            let mut runs = Vec::new();
+            let mut run: Option<(u32, u32)> = None;
+            for sector in start..=start + count {
+                let wanted = sector < start + count
+                    && !self.cache.contains(sector)
+                    && !readahead.is_tracked(sector);
+                match run {
+                    Some((run_start, run_count)) if wanted => {
+                        run = Some((run_start, run_count + 1));
+                    }
+                    None if wanted => run = Some((sector, 1)),
+                    Some((run_start, run_count)) => {
+                        readahead.on_queued(process_id, run_start, run_count);
+                        runs.push((run_start, run_count));
+                        run = None;
+                    }
+                    None => {}
+                }
+            }
+
+            for (run_start, run_count) in runs {
+                println!(
+                    "[Time {}] READAHEAD: Prefetching sectors {}..{} for process {}.",
+                    self.current_time,
+                    run_start,
+                    run_start + run_count,
+                    process_id
+                );
+                self.queue(Request {
+                    io_class,
+                    ..Request::prefetch(run_start, run_count, self.current_time)
+                });
+            }
+        }
+
+        /// Returns true if the write completed without waiting for the disk.
+        fn issue_write(&mut self, request: Request, outcome: CacheOutcome) -> bool {
+            match self.write_policy {
+                WritePolicy::WriteBack => {
+                    // Writes complete into the cache, a write miss needs no disk read
+                    println!(
+                        "[Time {}] CACHE: Sector {} written to cache{}.",
+                        self.current_time,
+                        request.sector,
+                        if outcome == CacheOutcome::Miss {
+                            " (write miss)"
                         } else {
-                            break;
+                            ""
                         }
-                        if let Some(next_request) =
-                            self.scheduler.get_next_request(self.current_time)
-                        {
-                            println!(
-                                "[Time {}] SCHEDULER: Retrieved request for sector {} ({:?})",
-                                self.current_time, next_request.sector, next_request.operation
-                            );
+                    );
+                    let evicted = self.cache.write(request.sector, self.current_time);
+                    self.copy_buffer(request.origin);
+                    self.stats
+                        .record_latency(request.operation, request.io_class, 1);
+                    self.handle_eviction(evicted);
+                    true
+                }
+                WritePolicy::WriteThrough => {
+                    println!(
+                        "[Time {}] CACHE: Sector {} written to cache and queued for disk.",
+                        self.current_time, request.sector
+                    );
+                    let evicted = self.cache.fill(request.sector);
+                    self.handle_eviction(evicted);
+                    self.stats.write_through += 1;
+                    self.queue(request);
+                    false
+                }
+                WritePolicy::WriteAround => {
+                    println!(
+                        "[Time {}] CACHE: Sector {} bypasses cache, queued for disk.",
+                        self.current_time, request.sector
+                    );
+                    if self.cache.remove(request.sector).is_some() {
+                        if let Some(readahead) = self.readahead.as_mut() {
+                            readahead.on_evict(request.sector);
                         }
-                        self.scheduler.print_queue_status();
                     }
+                    self.stats.write_around += 1;
+                    self.queue(request);
+                    false
+                }
+            }
+        }
+
+        /// Resets the zone holding the requested sector. The zone's data is gone, so its
+        /// cached buffers are dropped without writing the dirty ones back.
+        fn reset_zone(&mut self, request: &Request) {
+            let zone = match self.volume.reset_zone(request.sector) {
+                Ok(zone) => zone,
+                Err(err) => return self.fail_request(request, err),
+            };
+            println!(
+                "[Time {}] CACHE: Zone {}..{} reset, dropping its cached sectors.",
+                self.current_time, zone.start, zone.end
+            );
+            for sector in zone {
+                if self.cache.remove(sector).is_some() {
+                    if let Some(readahead) = self.readahead.as_mut() {
+                        readahead.on_evict(sector);
+                    }
+                }
+            }
+            self.stats.zone_resets += 1;
+        }
+
+        /// Queues a write if the buffer evicted from the cache was dirty.
+        fn handle_eviction(&mut self, evicted: Option<Buffer>) {
+            let Some(buffer) = evicted else {
+                return;
+            };
+            if let Some(readahead) = self.readahead.as_mut() {
+                readahead.on_evict(buffer.sector);
+            }
+            if buffer.dirty {
+                println!(
+                    "[Time {}] CACHE: Dirty sector {} evicted, writing back.",
+                    self.current_time, buffer.sector
+                );
+                self.stats.writebacks += 1;
+                self.queue(Request::writeback(buffer.sector, self.current_time));
+            }
+        }
+
+        /// Periodically queues writes for the dirty buffers older than `dirty_expire`.
+        /// The flusher wakes up every `flush_interval` ms counted from time 0.
+        fn run_flusher(&mut self) {
+            let interval = self.flush_interval.max(1);
+            if self.current_time - self.last_flush < interval {
+                return;
+            }
+            self.last_flush = self.current_time - self.current_time % interval;
+            self.flush(self.dirty_expire);
+        }
+
+        /// Next time the flusher wakes up with a dirty buffer to write back.
+        fn next_flush(&mut self) -> Option<u32> {
+            let due = self.cache.oldest_dirty()? + self.dirty_expire;
+            let interval = self.flush_interval.max(1);
+            let wake = due.max(self.last_flush + interval).div_ceil(interval) * interval;
+            Some(wake)
+        }
+
+        /// Writes every dirty buffer back, used when the simulation ends.
+        fn sync(&mut self) {
+            self.flush(0);
+            self.dispatch_requests();
+        }
+
+        fn flush(&mut self, max_age: u32) {
+            for sector in self.cache.flush_expired(self.current_time, max_age) {
+                println!(
+                    "[Time {}] FLUSHER: Writing back dirty sector {}.",
+                    self.current_time, sector
+                );
+                self.stats.flushed += 1;
+                self.queue(Request::writeback(sector, self.current_time));
+            }
+        }
+
+        fn queue(&mut self, request: Request) {
+            match self.volume.submit(request.clone()) {
+                Ok(merges) => self.stats.record_queued(merges),
+                Err(err) => self.fail_request(&request, err),
+            }
+        }
+
+        /// Reports a request the volume could not serve. Prefetched sectors that will
+        /// not arrive are no longer waited for.
+        fn fail_request(&mut self, request: &Request, err: DeviceError) {
+            println!(
+                "[ERROR] {:?} of sector {} x{} failed: {}",
+                request.operation, request.sector, request.count, err
+            );
+            self.stats.io_errors += 1;
+            if let Some(readahead) = self.readahead.as_mut() {
+                for sector in request.sector..request.end() {
+                    readahead.on_failed(sector);
+                }
+            }
+        }
 
-                    if process.has_requests() {
-                        println!(
-                            "[Time {}] Process {} moved to end of run queue.",
-                            self.current_time, process.id
-                        );
-                        process.time_remaining = self.quantum_time;
-                        self.process_manager.move_to_sleep(process.id);
-                    } else {
-                        println!(
-                            "[Time {}] Process {} completed.",
-                            self.current_time, process.id
-                        );
+        /// Lets the simulated time pass, completing the disk requests that finish
+        /// on the way and keeping the disks busy with queued ones.
+        /// The flusher wakes up on the way too, so dirty buffers never stay past their age.
+        fn advance_to(&mut self, time: u32) {
+            loop {
+                let completion = self.volume.next_completion().filter(|&t| t <= time);
+                let flush = self.next_flush().filter(|&t| t <= time);
+                let Some(next) = completion.into_iter().chain(flush).min() else {
+                    break;
+                };
+                self.current_time = self.current_time.max(next);
+                if completion == Some(next) {
+                    for (request, result) in self.volume.complete(next) {
+                        self.complete_request(&request, result);
                     }
-                } else if !self.process_manager.sleep_q.is_empty() {
-                    if let Some(sleeping_process) = self.process_manager.sleep_q.pop_front() {
-                        println!(
-                            "[Time {}] Waking up process {} from sleep queue.",
-                            self.current_time, sleeping_process.id
-                        );
-                        self.process_manager.wake_up_process(sleeping_process.id);
+                }
+                self.run_flusher();
+                self.start_requests();
+            }
+            self.current_time = self.current_time.max(time);
+        }
+
+        /// A cache hit copies the buffer to or from the process, 1ms of its CPU time.
+        fn copy_buffer(&mut self, origin: RequestOrigin) {
+            if let RequestOrigin::Process(process_id) = origin {
+                if let Some(process) = self.process_manager.get_mut(process_id) {
+                    process.charge_cpu(1);
+                }
+            }
+            self.stats.cpu_busy += 1;
+            self.advance_to(self.current_time + 1);
+        }
+
+        /// Serves every queued request, used when the simulation ends.
+        fn dispatch_requests(&mut self) {
+            while self.volume.has_work() {
+                self.start_requests();
+                let Some(completion) = self.volume.next_completion() else {
+                    println!("[ERROR] Volume has unfinished requests but no disk is busy.");
+                    break;
+                };
+                self.advance_to(completion);
+            }
+        }
+
+        /// Hands the next queued request to every idle disk.
+        fn start_requests(&mut self) {
+            for (_, request, disk_time) in self.volume.start_idle_members(self.current_time) {
+                self.stats.dispatched += 1;
+                self.stats.sectors_transferred += request.count;
+                self.stats.disk_time += disk_time;
+                match request.operation {
+                    IoOperation::Read => self.stats.disk_reads += request.count,
+                    IoOperation::Write => self.stats.disk_writes += request.count,
+                    IoOperation::ZoneReset => {}
+                }
+                if request.origin == RequestOrigin::Prefetch {
+                    if let Some(readahead) = self.readahead.as_mut() {
+                        readahead.stats.disk_time += disk_time;
                     }
-                } else {
+                }
+            }
+        }
+
+        /// The volume finished a request, all of its member requests are done. A failed
+        /// read leaves the cache alone, whoever waits for the request is woken either way.
+        fn complete_request(&mut self, request: &Request, result: Result<(), DeviceError>) {
+            let failed = result.is_err();
+            if let Err(err) = result {
+                self.fail_request(request, err);
+            } else if request.operation == IoOperation::Read {
+                for sector in request.sector..request.end() {
+                    let evicted = self.cache.fill(sector);
+                    self.handle_eviction(evicted);
+                    if let Some(readahead) = self.readahead.as_mut() {
+                        readahead.on_fill(sector);
+                    }
+                }
+                self.cache.record_occupancy(self.current_time);
+            }
+            self.wake_blocked(request, failed);
+        }
+
+        /// Completes the process requests covered by the request the volume just served,
+        /// the request itself or reads waiting for the sectors it prefetched. Failed
+        /// requests are left out of the latency stats.
+        fn wake_blocked(&mut self, request: &Request, failed: bool) {
+            let done = self.process_manager.complete_io(request);
+            for waiter in done {
+                if let RequestOrigin::Process(process_id) = waiter.origin {
                     println!(
-                        "\n[Time {}] SCHEDULER: No more requests to process.",
-                        self.current_time
+                        "[Time {}] Process {} unblocked, sector {} done.",
+                        self.current_time, process_id, waiter.sector
+                    );
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.record_completion(process_id, self.current_time);
+                    }
+                }
+                if !failed {
+                    self.stats.record_latency(
+                        waiter.operation,
+                        waiter.io_class,
+                        self.current_time - waiter.issued_at,
                     );
-                    break;
                 }
             }
+        }
+    }
 
-            println!("\n[Time {}] All processes completed.", self.current_time);
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::cpu::cpu::Cfs;
+        use crate::device::device::BlockDevice;
+        use crate::disk::disk::Disk;
+        use crate::policies::policies::LruCache;
+        use crate::process::process::Process;
+        use crate::scheduler::scheduler::FifoScheduler;
+        use crate::trace::trace::{Trace, TraceFormat, TraceMapping};
+        use crate::volume::volume::RaidLevel;
+        use crate::zoned::zoned::ZonedDisk;
+
+        fn system(write_policy: WritePolicy) -> System<FifoScheduler, LruCache> {
+            let devices: Vec<Box<dyn BlockDevice>> = vec![Box::new(Disk::new(5, 100, 2, 4, 1))];
+            let volume = Volume::new(RaidLevel::Single, devices, FifoScheduler::new(8)).unwrap();
+            System::new(LruCache::new(6), volume)
+                .with_write_policy(write_policy)
+                .with_readahead(2, 4)
+        }
+
+        #[test]
+        fn write_around_drops_prefetched_sector() {
+            // Reading 10 and 11 prefetches 12, the write to 12 then bypasses the cache
+            // and drops the prefetched copy, so the last read has to go to the disk
+            let mut system = system(WritePolicy::WriteAround);
+            let requests = vec![
+                Request::new(10, IoOperation::Read),
+                Request::new(11, IoOperation::Read),
+                Request::new(12, IoOperation::Write),
+                Request::new(12, IoOperation::Read),
+            ];
+            system
+                .process_manager
+                .add_process(Process::new(1, requests, 20).with_think_times([0, 1, 10, 10]));
+            system.run();
+
+            assert!(!system.process_manager.has_live_processes());
+            let readahead = system.readahead.as_ref().unwrap();
+            assert!(!readahead.prefetched.contains_key(&12));
+            assert_eq!(readahead.stats.wasted, 1);
+        }
+
+        /// Writes sector 10 three times, then six other sectors that push it out of the cache.
+        fn write_traffic(write_policy: WritePolicy) -> System<FifoScheduler, LruCache> {
+            let mut system = system(write_policy);
+            let sectors = [10, 10, 10, 20, 21, 22, 23, 24, 25];
+            let requests = sectors
+                .iter()
+                .map(|&sector| Request::new(sector, IoOperation::Write))
+                .collect();
+            system
+                .process_manager
+                .add_process(Process::new(1, requests, 20));
+            system.run();
+            system
+        }
+
+        #[test]
+        fn write_through_writes_every_time() {
+            let system = write_traffic(WritePolicy::WriteThrough);
+            assert_eq!(system.stats.write_through, 9);
+            assert_eq!(system.stats.disk_writes, 9);
+            assert_eq!(system.stats.writebacks + system.stats.flushed, 0);
+        }
+
+        #[test]
+        fn write_around_skips_the_cache() {
+            let mut system = write_traffic(WritePolicy::WriteAround);
+            assert_eq!(system.stats.write_around, 9);
+            assert_eq!(system.stats.disk_writes, 9);
+            assert!(!system.cache.contains(10));
+            assert_eq!(system.cache.oldest_dirty(), None);
+        }
+
+        #[test]
+        fn write_back_writes_on_eviction_or_flush() {
+            // Sector 10 is written once when evicted, the six sectors left dirty
+            // in the cache are written by the final sync
+            let system = write_traffic(WritePolicy::WriteBack);
+            assert_eq!(system.stats.write_through + system.stats.write_around, 0);
+            assert_eq!(system.stats.writebacks, 1);
+            assert_eq!(system.stats.flushed, 6);
+            assert_eq!(system.stats.disk_writes, 7);
+        }
+
+        #[test]
+        fn flusher_catches_up_after_idle_gap() {
+            // Dirtied at 0 and due at 100, the flusher wakes up at 100 even though
+            // nothing else happens until 1000
+            let mut system = system(WritePolicy::WriteBack);
+            system.cache.write(10, 0);
+            system.advance_to(1000);
+            assert_eq!(system.stats.flushed, 1);
+            assert_eq!(system.last_flush, 100);
+            assert_eq!(system.volume.members[0].stats.sectors_written, 1);
+            assert_eq!(system.cache.oldest_dirty(), None);
+        }
+
+        #[test]
+        fn flusher_leaves_young_dirty_buffers() {
+            let mut system = system(WritePolicy::WriteBack);
+            system.cache.write(10, 0);
+            system.cache.write(11, 80);
+            system.advance_to(150);
+            assert_eq!(system.stats.flushed, 1);
+            assert_eq!(system.cache.oldest_dirty(), Some(80));
+            system.advance_to(200);
+            assert_eq!(system.stats.flushed, 2);
+        }
+
+        #[test]
+        fn zone_reset_lets_a_process_rewrite_the_zone() {
+            let disk = Disk::new(5, 100, 2, 4, 1);
+            let devices: Vec<Box<dyn BlockDevice>> = vec![Box::new(ZonedDisk::new(disk, 50, 4))];
+            let volume = Volume::new(RaidLevel::Single, devices, FifoScheduler::new(8)).unwrap();
+            let mut system =
+                System::new(LruCache::new(6), volume).with_write_policy(WritePolicy::WriteThrough);
+            let requests = [50, 51, 55, 50]
+                .into_iter()
+                .zip([
+                    IoOperation::Write,
+                    IoOperation::Write,
+                    IoOperation::ZoneReset,
+                    IoOperation::Write,
+                ])
+                .map(|(sector, operation)| Request::new(sector, operation))
+                .collect();
+            system
+                .process_manager
+                .add_process(Process::new(1, requests, 20));
+            system.run();
+
+            // Without the reset, the second write to 50 would be off the write pointer
+            assert_eq!(system.stats.zone_resets, 1);
+            assert_eq!(system.stats.io_errors, 0);
+            assert!(!system.cache.contains(51));
+            assert_eq!(system.stats.write_latency.count, 3);
+        }
+
+        #[test]
+        fn failed_requests_stay_out_of_latency_stats() {
+            // The volume ends at sector 500
+            let mut system = system(WritePolicy::WriteBack);
+            let requests = vec![
+                Request::new(600, IoOperation::Read),
+                Request::new(10, IoOperation::Read),
+            ];
+            system
+                .process_manager
+                .add_process(Process::new(1, requests, 20));
+            system.run();
+
+            assert_eq!(system.stats.io_errors, 1);
+            assert_eq!(system.stats.read_latency.count, 1);
+            assert!(system.stats.read_latency.max > 0);
+        }
+
+        /// Runs the processes under CFS, returns the end time, the stats and the CPU
+        /// time of each process.
+        fn run(processes: Vec<Process>, record: Option<&str>) -> (u32, String, Vec<u32>) {
+            let mut system =
+                system(WritePolicy::WriteBack).with_cpu_scheduler(Box::new(Cfs::new(40, 5)));
+            if let Some(path) = record {
+                system = system.with_recorder(path);
+            }
+            for process in processes {
+                system.process_manager.add_process(process);
+            }
+            system.run();
+            let cpu_times = system
+                .process_manager
+                .processes
+                .values()
+                .map(|process| process.cpu_time)
+                .collect();
+            (
+                system.current_time,
+                format!("{:?}", system.stats),
+                cpu_times,
+            )
+        }
+
+        #[test]
+        fn recorded_run_replays_to_same_report() {
+            let read = |sector| Request::new(sector, IoOperation::Read);
+            let write = |sector| Request::new(sector, IoOperation::Write);
+            let processes = vec![
+                Process::new(1, vec![read(10), write(40), read(11)], 20)
+                    .with_think_times([0, 4, 0])
+                    .with_cpu_bursts([5, 30, 2]),
+                Process::new(2, vec![write(10), read(70)], 7)
+                    .with_nice(5)
+                    .with_cpu_bursts([12, 3]),
+                Process::new(3, vec![read(90), read(91), read(300)], 20)
+                    .with_io_class(IoClass::Idle)
+                    .with_think_times([3, 0, 8])
+                    .with_cpu_bursts([1, 1, 1]),
+            ];
+            let path = std::env::temp_dir().join(format!("replay-{}.trace", std::process::id()));
+            let path = path.to_str().unwrap();
+            let recorded = run(processes, Some(path));
+
+            let trace = Trace::load(path, TraceFormat::Recorded);
+            let _ = std::fs::remove_file(path);
+            let capacity = system(WritePolicy::WriteBack).volume.capacity();
+            let replayed = run(
+                trace.unwrap().processes(&TraceMapping::new(capacity), 20),
+                None,
+            );
+            assert_eq!(replayed, recorded);
         }
     }
 }
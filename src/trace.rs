@@ -0,0 +1,597 @@
+pub mod trace {
+    use crate::cache::cache::CacheOutcome;
+    use crate::process::process::Process;
+    use crate::scheduler::scheduler::{IoClass, IoOperation, Request, RequestOrigin};
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::fmt::Write as _;
+
+    const SECTOR_BYTES: u64 = 512;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TraceFormat {
+        Spc,      // SPC-1 ASCII: ASU,LBA,size in bytes,opcode,timestamp in seconds
+        Msr,      // MSR Cambridge CSV: 100ns ticks,host,disk,type,offset,size,response time
+        Blkparse, // Default text output of blkparse, only queue (Q) events are used
+        Recorded, // Written by `TraceRecorder`
+    }
+
+    impl TraceFormat {
+        pub fn from_name(name: &str) -> Option<Self> {
+            match name {
+                "spc" => Some(TraceFormat::Spc),
+                "msr" => Some(TraceFormat::Msr),
+                "blkparse" => Some(TraceFormat::Blkparse),
+                "replay" => Some(TraceFormat::Recorded),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum TraceError {
+        Io(std::io::Error),
+        Parse { line: usize, reason: String },
+        Empty, // No usable record in the trace
+    }
+
+    impl fmt::Display for TraceError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                TraceError::Io(err) => write!(f, "cannot read trace: {}", err),
+                TraceError::Parse { line, reason } => write!(f, "line {}: {}", line, reason),
+                TraceError::Empty => write!(f, "trace has no read or write records"),
+            }
+        }
+    }
+
+    impl std::error::Error for TraceError {}
+
+    /// One I/O of a trace, in the units of the trace.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct TraceRecord {
+        pub time: f64,              // Milliseconds
+        pub completed: Option<f64>, // Known for recorded runs
+        pub lba: u64,               // 512 byte sectors
+        pub count: u32,
+        pub operation: IoOperation,
+        pub stream: String,          // ASU, disk or process the I/O came from
+        pub process_id: Option<u32>, // Process of a recorded run, kept on replay
+        pub step: Option<RecordedStep>,
+    }
+
+    /// What the process of a recorded run did before an I/O, restored on replay.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RecordedStep {
+        pub think_time: u32,
+        pub cpu_burst: u32,
+        pub nice: i32,
+        pub io_class: IoClass,
+        pub quantum: u32,
+    }
+
+    /// Where traced addresses land on the simulated volume.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum AddressMapping {
+        Wrap,  // Addresses past the end wrap around, nearby I/Os stay nearby
+        Scale, // The whole traced address range is squeezed onto the volume
+    }
+
+    /// How a trace is fitted onto the simulated volume.
+    #[derive(Debug, Clone, Copy)]
+    pub struct TraceMapping {
+        pub capacity: u32,
+        pub address: AddressMapping,
+        pub time_scale: Option<f64>, // Multiplies the gaps between I/Os when set
+        pub max_sectors: Option<u32>, // Longer I/Os are cut short when set
+    }
+
+    impl TraceMapping {
+        pub fn new(capacity: u32) -> Self {
+            Self {
+                capacity: capacity.max(1),
+                address: AddressMapping::Wrap,
+                time_scale: None,
+                max_sectors: None,
+            }
+        }
+
+        /// Caps the sectors taken from each traced I/O, `None` keeps them all.
+        pub fn with_max_sectors(mut self, max_sectors: Option<u32>) -> Self {
+            self.max_sectors = max_sectors.map(|max| max.max(1));
+            self
+        }
+
+        pub fn with_address_mapping(mut self, address: AddressMapping) -> Self {
+            self.address = address;
+            self
+        }
+
+        pub fn with_time_scale(mut self, time_scale: f64) -> Self {
+            self.time_scale = Some(time_scale);
+            self
+        }
+    }
+
+    pub struct Trace {
+        pub format: TraceFormat,
+        pub records: Vec<TraceRecord>,
+    }
+
+    impl Trace {
+        pub fn load(path: &str, format: TraceFormat) -> Result<Self, TraceError> {
+            let text = std::fs::read_to_string(path).map_err(TraceError::Io)?;
+            Self::parse(&text, format)
+        }
+
+        pub fn parse(text: &str, format: TraceFormat) -> Result<Self, TraceError> {
+            let mut records = Vec::new();
+            for (index, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let record = match format {
+                    TraceFormat::Spc => parse_spc(line),
+                    TraceFormat::Msr => parse_msr(line),
+                    TraceFormat::Blkparse => parse_blkparse(line),
+                    TraceFormat::Recorded => parse_recorded(line),
+                }
+                .map_err(|reason| TraceError::Parse {
+                    line: index + 1,
+                    reason,
+                })?;
+                records.extend(record);
+            }
+            if records.is_empty() {
+                return Err(TraceError::Empty);
+            }
+            records.sort_by(|a, b| a.time.total_cmp(&b.time));
+            Ok(Self { format, records })
+        }
+
+        /// Volume sector and length of a record.
+        fn map_address(
+            &self,
+            record: &TraceRecord,
+            mapping: &TraceMapping,
+            end: u64,
+        ) -> (u32, u32) {
+            let capacity = mapping.capacity as u64;
+            let sector = match mapping.address {
+                AddressMapping::Wrap => record.lba % capacity,
+                AddressMapping::Scale => record.lba * capacity / end.max(1),
+            };
+            let count = (record.count as u64)
+                .min(mapping.max_sectors.map_or(u64::MAX, u64::from))
+                .min(capacity - sector);
+            (sector as u32, count as u32)
+        }
+
+        /// Builds one process per stream. Each traced I/O becomes single sector requests
+        /// the schedulers merge again, the gap since the previous I/O of the stream
+        /// becomes think time. Recorded runs keep their sectors, and their processes get
+        /// back the think time, CPU bursts, nice value, I/O class and quantum they had.
+        /// Older recordings without those fall back to the gap since the previous I/O
+        /// completed.
+        pub fn processes(&self, mapping: &TraceMapping, quantum: u32) -> Vec<Process> {
+            let start = self.records[0].time;
+            let end = self
+                .records
+                .iter()
+                .map(|record| record.lba + record.count as u64)
+                .max()
+                .unwrap_or(1);
+            let mut streams: Vec<Stream> = Vec::new();
+            let mut index_of: HashMap<&str, usize> = HashMap::new();
+            let mut truncated = 0;
+            let time_scale = mapping.time_scale.unwrap_or(1.0);
+            let scale = |time: f64| ((time - start) * time_scale).round() as u32;
+            for record in &self.records {
+                let time = scale(record.time);
+                let index = *index_of.entry(&record.stream).or_insert_with(|| {
+                    streams.push(Stream {
+                        first: record,
+                        requests: Vec::new(),
+                        think_times: Vec::new(),
+                        cpu_bursts: Vec::new(),
+                        last_time: 0,
+                    });
+                    streams.len() - 1
+                });
+                let stream = &mut streams[index];
+                let (sector, count) = match record.step {
+                    Some(_) => (record.lba as u32, record.count),
+                    None => self.map_address(record, mapping, end),
+                };
+                if count < record.count {
+                    truncated += 1;
+                }
+                let (think_time, cpu_burst) = match record.step {
+                    Some(step) => (
+                        (step.think_time as f64 * time_scale).round() as u32,
+                        step.cpu_burst,
+                    ),
+                    None => (time.saturating_sub(stream.last_time), 0),
+                };
+                for offset in 0..count {
+                    stream
+                        .requests
+                        .push(Request::new(sector + offset, record.operation));
+                    let first = offset == 0;
+                    stream.think_times.push(if first { think_time } else { 0 });
+                    stream.cpu_bursts.push(if first { cpu_burst } else { 0 });
+                }
+                stream.last_time = record.completed.map_or(time, scale);
+            }
+            if truncated > 0 {
+                println!(
+                    "[TRACE] {} of {} records cut short by the size limit or the end of the volume.",
+                    truncated,
+                    self.records.len()
+                );
+            }
+            // A recorded run added its processes by id, the CPU queue starts in that order
+            streams.sort_by_key(|stream| stream.first.process_id);
+
+            streams
+                .into_iter()
+                .enumerate()
+                .map(|(index, stream)| {
+                    let first = stream.first;
+                    let process_id = first.process_id.unwrap_or(index as u32 + 1);
+                    println!(
+                        "[TRACE] Stream {} -> process {} with {} requests.",
+                        first.stream,
+                        process_id,
+                        stream.requests.len()
+                    );
+                    let process = Process::new(
+                        process_id,
+                        stream.requests,
+                        first.step.map_or(quantum, |step| step.quantum),
+                    )
+                    .with_think_times(stream.think_times)
+                    .with_cpu_bursts(stream.cpu_bursts);
+                    match first.step {
+                        Some(step) => process.with_nice(step.nice).with_io_class(step.io_class),
+                        None => process,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    /// Requests of one traced stream while its process is being built.
+    struct Stream<'a> {
+        first: &'a TraceRecord,
+        requests: Vec<Request>,
+        think_times: Vec<u32>,
+        cpu_bursts: Vec<u32>,
+        last_time: u32, // Trace time the stream's previous I/O finished
+    }
+
+    fn operation(code: &str) -> Result<IoOperation, String> {
+        match code.to_ascii_lowercase().as_str() {
+            "r" | "read" => Ok(IoOperation::Read),
+            "w" | "write" => Ok(IoOperation::Write),
+            "z" | "reset" => Ok(IoOperation::ZoneReset),
+            _ => Err(format!("unknown operation {:?}", code)),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(field: Option<&str>, name: &str) -> Result<T, String> {
+        let field = field.ok_or_else(|| format!("missing {}", name))?.trim();
+        field
+            .parse()
+            .map_err(|_| format!("{} {:?} is not a number", name, field))
+    }
+
+    fn sectors(bytes: u64) -> u32 {
+        bytes.div_ceil(SECTOR_BYTES).max(1) as u32
+    }
+
+    /// `0,20941264,8192,W,0.551706`
+    fn parse_spc(line: &str) -> Result<Option<TraceRecord>, String> {
+        let mut fields = line.split(',');
+        let asu: u32 = number(fields.next(), "ASU")?;
+        let lba: u64 = number(fields.next(), "LBA")?;
+        let size: u64 = number(fields.next(), "size")?;
+        let operation = operation(fields.next().ok_or("missing opcode")?.trim())?;
+        let seconds: f64 = number(fields.next(), "timestamp")?;
+        Ok(Some(TraceRecord {
+            time: seconds * 1000.0,
+            completed: None,
+            lba,
+            count: sectors(size),
+            operation,
+            stream: format!("asu{}", asu),
+            process_id: None,
+            step: None,
+        }))
+    }
+
+    /// `128166372003061629,hm,1,Read,9170825216,4096,41286`
+    fn parse_msr(line: &str) -> Result<Option<TraceRecord>, String> {
+        let mut fields = line.split(',');
+        let ticks: u64 = number(fields.next(), "timestamp")?;
+        let host = fields.next().ok_or("missing host name")?.trim();
+        let disk: u32 = number(fields.next(), "disk number")?;
+        let operation = operation(fields.next().ok_or("missing type")?.trim())?;
+        let offset: u64 = number(fields.next(), "offset")?;
+        let size: u64 = number(fields.next(), "size")?;
+        Ok(Some(TraceRecord {
+            time: ticks as f64 / 10_000.0,
+            completed: None,
+            lba: offset / SECTOR_BYTES,
+            count: sectors(size),
+            operation,
+            stream: format!("{}_{}", host, disk),
+            process_id: None,
+            step: None,
+        }))
+    }
+
+    /// `  8,0    3        1     0.000000000   697  Q   W 223490 + 8 [kjournald]`
+    /// Other events, discards, flushes and the summary at the end are skipped.
+    fn parse_blkparse(line: &str) -> Result<Option<TraceRecord>, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 || !fields[0].contains(',') || fields[5] != "Q" || fields[8] != "+" {
+            return Ok(None);
+        }
+        let operation = match (fields[6].contains('R'), fields[6].contains('W')) {
+            (true, false) => IoOperation::Read,
+            (false, true) => IoOperation::Write,
+            _ => return Ok(None),
+        };
+        let seconds: f64 = number(Some(fields[3]), "timestamp")?;
+        let pid: u32 = number(Some(fields[4]), "pid")?;
+        Ok(Some(TraceRecord {
+            time: seconds * 1000.0,
+            completed: None,
+            lba: number(Some(fields[7]), "sector")?,
+            count: number::<u32>(Some(fields[9]), "sector count")?.max(1),
+            operation,
+            stream: format!("pid{}", pid),
+            process_id: None,
+            step: None,
+        }))
+    }
+
+    fn io_class(name: &str) -> Result<IoClass, String> {
+        match name {
+            "realtime" => Ok(IoClass::RealTime),
+            "idle" => Ok(IoClass::Idle),
+            _ => name
+                .strip_prefix("best-effort/")
+                .and_then(|level| level.parse().ok())
+                .filter(|&level| level < IoClass::BEST_EFFORT_LEVELS)
+                .map(IoClass::BestEffort)
+                .ok_or_else(|| format!("unknown I/O class {:?}", name)),
+        }
+    }
+
+    /// `12 40 100 R 1 miss 0 3 5 0 best-effort/4 20`, see `TraceRecorder::save`. The
+    /// last five fields are missing in recordings made before they were added.
+    fn parse_recorded(line: &str) -> Result<Option<TraceRecord>, String> {
+        let mut fields = line.split_whitespace();
+        let issued: u32 = number(fields.next(), "issue time")?;
+        let completed = match fields.next() {
+            Some("-") => None,
+            field => Some(number::<u32>(field, "completion time")? as f64),
+        };
+        let lba: u64 = number(fields.next(), "sector")?;
+        let operation = operation(fields.next().ok_or("missing operation")?)?;
+        let process: u32 = number(fields.next(), "process")?;
+        let mut fields = fields.skip(2); // Cache outcome and head position
+        let step = match fields.next() {
+            None => None,
+            think_time => Some(RecordedStep {
+                think_time: number(think_time, "think time")?,
+                cpu_burst: number(fields.next(), "CPU burst")?,
+                nice: number(fields.next(), "nice value")?,
+                io_class: io_class(fields.next().ok_or("missing I/O class")?)?,
+                quantum: number(fields.next(), "quantum")?,
+            }),
+        };
+        Ok(Some(TraceRecord {
+            time: issued as f64,
+            completed,
+            lba,
+            count: 1,
+            operation,
+            stream: format!("process{}", process),
+            process_id: Some(process),
+            step,
+        }))
+    }
+
+    /// A request a process issued during a recorded run.
+    #[derive(Debug, Clone)]
+    pub struct RecordedRequest {
+        pub issued_at: u32,
+        pub completed_at: Option<u32>,
+        pub sector: u32,
+        pub operation: IoOperation,
+        pub process_id: u32,
+        pub outcome: CacheOutcome,
+        pub head_sector: u32, // Head of the disk holding the sector when it was issued
+        pub step: RecordedStep,
+    }
+
+    /// Records every request the processes issue, for replay with another
+    /// configuration.
+    pub struct TraceRecorder {
+        pub path: String,
+        pub requests: Vec<RecordedRequest>,
+        pub spent: HashMap<u32, (u32, u32)>, // Process -> think and CPU time since its last request
+    }
+
+    impl TraceRecorder {
+        pub fn new(path: &str) -> Self {
+            Self {
+                path: path.to_string(),
+                requests: Vec::new(),
+                spent: HashMap::new(),
+            }
+        }
+
+        pub fn record_think(&mut self, process_id: u32, time: u32) {
+            self.spent.entry(process_id).or_default().0 += time;
+        }
+
+        pub fn record_cpu(&mut self, process_id: u32, time: u32) {
+            self.spent.entry(process_id).or_default().1 += time;
+        }
+
+        pub fn record_issue(
+            &mut self,
+            request: &Request,
+            process: &Process,
+            outcome: CacheOutcome,
+            head_sector: u32,
+        ) {
+            let RequestOrigin::Process(process_id) = request.origin else {
+                return;
+            };
+            let (think_time, cpu_burst) = self.spent.remove(&process_id).unwrap_or_default();
+            self.requests.push(RecordedRequest {
+                issued_at: request.issued_at,
+                completed_at: None,
+                sector: request.sector,
+                operation: request.operation,
+                process_id,
+                outcome,
+                head_sector,
+                step: RecordedStep {
+                    think_time,
+                    cpu_burst,
+                    nice: process.nice,
+                    io_class: request.io_class,
+                    quantum: process.quantum,
+                },
+            });
+        }
+
+        /// The last request of the process is done.
+        pub fn record_completion(&mut self, process_id: u32, time: u32) {
+            if let Some(request) = self
+                .requests
+                .iter_mut()
+                .rev()
+                .find(|request| request.process_id == process_id)
+            {
+                request.completed_at.get_or_insert(time);
+            }
+        }
+
+        /// One line per request: issue time, completion time, sector, operation,
+        /// process, cache outcome, head position, then think time and CPU burst before
+        /// the request and the nice value, I/O class and quantum of the process.
+        pub fn save(&self) -> std::io::Result<()> {
+            let mut text = String::from(
+                "# issued completed sector op process outcome head think cpu nice class quantum\n",
+            );
+            for request in &self.requests {
+                let completed = request
+                    .completed_at
+                    .map_or("-".to_string(), |time| time.to_string());
+                let operation = match request.operation {
+                    IoOperation::Read => "R",
+                    IoOperation::Write => "W",
+                    IoOperation::ZoneReset => "Z",
+                };
+                let outcome = match request.outcome {
+                    CacheOutcome::Hit { segment } => format!("hit:{}", segment),
+                    CacheOutcome::Miss => "miss".to_string(),
+                };
+                let step = &request.step;
+                let _ = writeln!(
+                    text,
+                    "{} {} {} {} {} {} {} {} {} {} {} {}",
+                    request.issued_at,
+                    completed,
+                    request.sector,
+                    operation,
+                    request.process_id,
+                    outcome,
+                    request.head_sector,
+                    step.think_time,
+                    step.cpu_burst,
+                    step.nice,
+                    step.io_class,
+                    step.quantum
+                );
+            }
+            std::fs::write(&self.path, text)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn spc_records() {
+            let trace = Trace::parse(
+                "0,20941264,8192,W,0.551706\n1,35,512,r,0.5",
+                TraceFormat::Spc,
+            );
+            let records = trace.unwrap().records;
+            assert_eq!(records.len(), 2);
+            assert_eq!((records[0].lba, records[0].count), (35, 1));
+            assert_eq!(records[0].operation, IoOperation::Read);
+            assert_eq!(records[0].stream, "asu1");
+            assert_eq!(records[1].time, 551.706);
+            assert_eq!((records[1].lba, records[1].count), (20941264, 16));
+            assert_eq!(records[1].operation, IoOperation::Write);
+
+            let err = Trace::parse("0,12,4096,X,0.1", TraceFormat::Spc).err();
+            assert!(matches!(err, Some(TraceError::Parse { line: 1, .. })));
+        }
+
+        #[test]
+        fn msr_records() {
+            let text = "128166372003061629,hm,1,Read,9170825216,4096,41286\n\
+                        128166372003071629,hm,0,Write,1024,700,2000";
+            let records = Trace::parse(text, TraceFormat::Msr).unwrap().records;
+            assert_eq!((records[0].lba, records[0].count), (17911768, 8));
+            assert_eq!(records[0].stream, "hm_1");
+            assert_eq!((records[1].lba, records[1].count), (2, 2));
+            assert_eq!(records[1].time - records[0].time, 1.0);
+        }
+
+        #[test]
+        fn blkparse_keeps_queue_events_only() {
+            let text = "  8,0    3        1     0.000000000   697  Q   W 223490 + 8 [kjournald]\n\
+                          8,0    3        2     0.000001000   697  C   W 223490 + 8 [0]\n\
+                          8,0    1        3     0.002000000   812  Q   D 1000 + 8 [fstrim]\n\
+                          8,0    1        4     0.003000000   812  Q  RA 4096 + 32 [cat]\n\
+                        CPU0 (8,0):\n\
+                         Reads Queued:           1,       16KiB";
+            let records = Trace::parse(text, TraceFormat::Blkparse).unwrap().records;
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].stream, "pid697");
+            assert_eq!((records[1].lba, records[1].count), (4096, 32));
+            assert_eq!(records[1].operation, IoOperation::Read);
+            assert_eq!(records[1].time, 3.0);
+
+            let summary_only = "CPU0 (8,0):\n Reads Queued: 1, 16KiB";
+            let err = Trace::parse(summary_only, TraceFormat::Blkparse).err();
+            assert!(matches!(err, Some(TraceError::Empty)));
+        }
+
+        #[test]
+        fn size_limit_is_optional() {
+            let trace = Trace::parse("0,0,8192,R,0", TraceFormat::Spc).unwrap();
+            let requests = |mapping: TraceMapping| trace.processes(&mapping, 20)[0].steps.len();
+            assert_eq!(requests(TraceMapping::new(100)), 16);
+            assert_eq!(
+                requests(TraceMapping::new(100).with_max_sectors(Some(8))),
+                8
+            );
+            // The end of the volume still cuts the I/O short
+            assert_eq!(requests(TraceMapping::new(10)), 10);
+        }
+    }
+}
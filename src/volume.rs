@@ -0,0 +1,802 @@
+pub mod volume {
+    use crate::device::device::{BlockDevice, DeviceError};
+    use crate::scheduler::scheduler::{IoOperation, MergeKind, Request, Scheduler};
+    use std::fmt;
+    use std::ops::Range;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RaidLevel {
+        Single,
+        Raid0 { chunk_sectors: u32 }, // Striping
+        Raid1,                        // Mirroring
+        Raid5 { chunk_sectors: u32 }, // Striping with rotating parity
+    }
+
+    impl RaidLevel {
+        pub fn min_members(&self) -> usize {
+            match self {
+                RaidLevel::Single => 1,
+                RaidLevel::Raid0 { .. } | RaidLevel::Raid1 => 2,
+                RaidLevel::Raid5 { .. } => 3,
+            }
+        }
+    }
+
+    impl fmt::Display for RaidLevel {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                RaidLevel::Single => write!(f, "single disk"),
+                RaidLevel::Raid0 { chunk_sectors } => {
+                    write!(f, "RAID 0 ({} sector chunks)", chunk_sectors)
+                }
+                RaidLevel::Raid1 => write!(f, "RAID 1"),
+                RaidLevel::Raid5 { chunk_sectors } => {
+                    write!(f, "RAID 5 ({} sector chunks)", chunk_sectors)
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum VolumeError {
+        TooFewMembers { level: RaidLevel, members: usize },
+        ZeroChunkSize,
+        TooLarge, // More logical sectors than a sector number can address
+    }
+
+    impl fmt::Display for VolumeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                VolumeError::TooFewMembers { level, members } => write!(
+                    f,
+                    "{} needs at least {} disks, got {}",
+                    level,
+                    level.min_members(),
+                    members
+                ),
+                VolumeError::ZeroChunkSize => write!(f, "chunk size must be at least one sector"),
+                VolumeError::TooLarge => write!(f, "volume has more than {} sectors", u32::MAX),
+            }
+        }
+    }
+
+    impl std::error::Error for VolumeError {}
+
+    #[derive(Debug, Default)]
+    pub struct MemberStats {
+        pub dispatched: u32,
+        pub sectors_read: u32,
+        pub sectors_written: u32,
+        pub busy_time: u32,
+        pub failed: u32, // Requests the device refused
+    }
+
+    /// One device of the volume with its own request queue.
+    pub struct Member<S: Scheduler> {
+        pub device: Box<dyn BlockDevice>,
+        pub scheduler: S,
+        pub in_flight: Option<Request>,
+        pub error: Option<DeviceError>, // Why the device refused `in_flight`
+        pub busy_until: u32,            // Completion time of `in_flight`
+        pub head_sector: u32,           // Sector the head stopped at, used by the scheduler
+        pub stats: MemberStats,
+    }
+
+    /// Sector of one member disk a logical request waits for.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Piece {
+        member: usize,
+        sector: u32,
+        operation: IoOperation,
+        dispatched: bool, // Part of the request the member has in flight
+    }
+
+    /// A logical request and the member sectors it still waits for. RAID 5 writes
+    /// first read the old data and parity, `then_write` holds the second phase.
+    struct PendingOp {
+        request: Request,
+        waiting: Vec<Piece>,
+        then_write: Vec<Piece>,
+        error: Option<DeviceError>, // First error a member reported for one of the pieces
+    }
+
+    /// Block device built from one or more disks. Logical requests are split into
+    /// single sector requests per member, each member scheduler merges them again.
+    pub struct Volume<S: Scheduler> {
+        pub level: RaidLevel,
+        pub members: Vec<Member<S>>,
+        pending: Vec<PendingOp>,
+    }
+
+    impl<S: Scheduler + Clone> Volume<S> {
+        /// Every member gets its own copy of `scheduler`.
+        pub fn new(
+            level: RaidLevel,
+            devices: Vec<Box<dyn BlockDevice>>,
+            scheduler: S,
+        ) -> Result<Self, VolumeError> {
+            if devices.len() < level.min_members() {
+                return Err(VolumeError::TooFewMembers {
+                    level,
+                    members: devices.len(),
+                });
+            }
+            if let RaidLevel::Raid0 { chunk_sectors: 0 } | RaidLevel::Raid5 { chunk_sectors: 0 } =
+                level
+            {
+                return Err(VolumeError::ZeroChunkSize);
+            }
+            let members = devices
+                .into_iter()
+                .map(|device| Member {
+                    device,
+                    scheduler: scheduler.clone(),
+                    in_flight: None,
+                    error: None,
+                    busy_until: 0,
+                    head_sector: 0,
+                    stats: MemberStats::default(),
+                })
+                .collect();
+            let volume = Self {
+                level,
+                members,
+                pending: Vec::new(),
+            };
+            if volume.logical_capacity().is_none() {
+                return Err(VolumeError::TooLarge);
+            }
+            Ok(volume)
+        }
+    }
+
+    impl<S: Scheduler> Volume<S> {
+        /// Logical sectors, limited by the smallest member.
+        pub fn capacity(&self) -> u32 {
+            // `new` rejects volumes whose capacity overflows
+            self.logical_capacity().unwrap_or(u32::MAX)
+        }
+
+        fn logical_capacity(&self) -> Option<u32> {
+            let smallest = self
+                .members
+                .iter()
+                .map(|member| member.device.capacity())
+                .min()
+                .unwrap_or(0);
+            let members = self.members.len() as u32;
+            match self.level {
+                RaidLevel::Single | RaidLevel::Raid1 => Some(smallest),
+                RaidLevel::Raid0 { .. } => smallest.checked_mul(members),
+                RaidLevel::Raid5 { .. } => smallest.checked_mul(members - 1),
+            }
+        }
+
+        /// Member and member sector holding a logical sector, for the striped layouts
+        /// the data member.
+        fn locate(&self, sector: u32) -> (usize, u32) {
+            let members = self.members.len() as u32;
+            match self.level {
+                RaidLevel::Single | RaidLevel::Raid1 => (0, sector),
+                RaidLevel::Raid0 { chunk_sectors } => {
+                    let chunk = sector / chunk_sectors;
+                    let member = chunk % members;
+                    let member_sector = chunk / members * chunk_sectors + sector % chunk_sectors;
+                    (member as usize, member_sector)
+                }
+                RaidLevel::Raid5 { chunk_sectors } => {
+                    let (data, _, member_sector) = self.locate_raid5(sector, chunk_sectors);
+                    (data, member_sector)
+                }
+            }
+        }
+
+        /// Data member, parity member and member sector of a logical sector. Parity
+        /// rotates backwards over the members, one stripe at a time.
+        fn locate_raid5(&self, sector: u32, chunk_sectors: u32) -> (usize, usize, u32) {
+            let members = self.members.len() as u32;
+            let chunk = sector / chunk_sectors;
+            let stripe = chunk / (members - 1);
+            let parity = members - 1 - stripe % members;
+            let mut data = chunk % (members - 1);
+            if data >= parity {
+                data += 1;
+            }
+            let member_sector = stripe * chunk_sectors + sector % chunk_sectors;
+            (data as usize, parity as usize, member_sector)
+        }
+
+        /// True if the request writes every data sector of the parity row holding
+        /// `sector`, so the new parity can be computed without reading anything.
+        fn covers_raid5_row(&self, request: &Request, sector: u32, chunk_sectors: u32) -> bool {
+            let data_members = self.members.len() as u32 - 1;
+            let stripe = sector / chunk_sectors / data_members;
+            let first = stripe * data_members * chunk_sectors + sector % chunk_sectors;
+            let last = first + (data_members - 1) * chunk_sectors;
+            request.sector <= first && last < request.end()
+        }
+
+        /// Head position of the member holding a logical sector.
+        pub fn head_sector(&self, sector: u32) -> u32 {
+            self.members[self.locate(sector).0].head_sector
+        }
+
+        /// Requests a member is still busy with, used to balance mirrored reads.
+        fn outstanding(&self, member: usize) -> usize {
+            let queued: usize = self
+                .pending
+                .iter()
+                .map(|op| op.waiting.iter().filter(|p| p.member == member).count())
+                .sum();
+            queued + self.members[member].in_flight.is_some() as usize
+        }
+
+        /// The least loaded mirror, the one with its head closest to the sector on a tie.
+        fn pick_mirror(&self, sector: u32) -> usize {
+            (0..self.members.len())
+                .min_by_key(|&member| {
+                    (
+                        self.outstanding(member),
+                        self.members[member].head_sector.abs_diff(sector),
+                    )
+                })
+                .unwrap_or(0)
+        }
+
+        /// Fails requests running past the last logical sector. Members are never
+        /// asked for sectors they do not have.
+        pub fn check_range(&self, request: &Request) -> Result<(), DeviceError> {
+            let capacity = self.capacity();
+            if request.sector as u64 + request.count as u64 > capacity as u64 {
+                return Err(DeviceError::OutOfRange {
+                    sector: request.sector,
+                    count: request.count,
+                    capacity,
+                });
+            }
+            Ok(())
+        }
+
+        /// Resets the zone holding a logical sector right away, outside the member
+        /// queues. Writes to the zone still queued land after the reset. Mirrors reset
+        /// the zone on every member, a striped volume has no zones of its own.
+        /// Returns the logical sectors of the zone.
+        pub fn reset_zone(&mut self, sector: u32) -> Result<Range<u32>, DeviceError> {
+            self.check_range(&Request::new(sector, IoOperation::ZoneReset))?;
+            match self.level {
+                RaidLevel::Single | RaidLevel::Raid1 => {
+                    let mut zone = sector..sector;
+                    for member in &mut self.members {
+                        zone = member.device.reset_zone(sector)?;
+                    }
+                    Ok(zone)
+                }
+                RaidLevel::Raid0 { .. } | RaidLevel::Raid5 { .. } => Err(DeviceError::NoZones),
+            }
+        }
+
+        /// Splits a logical request over the members and queues the pieces.
+        /// Returns how each queued piece was merged.
+        pub fn submit(&mut self, request: Request) -> Result<Vec<Option<MergeKind>>, DeviceError> {
+            self.check_range(&request)?;
+            let mut waiting = Vec::new();
+            let mut then_write = Vec::new();
+            for sector in request.sector..request.end() {
+                let operation = request.operation;
+                match (self.level, operation) {
+                    (RaidLevel::Raid1, IoOperation::Read) => waiting.push(Piece {
+                        member: self.pick_mirror(sector),
+                        sector,
+                        operation,
+                        dispatched: false,
+                    }),
+                    (RaidLevel::Raid1, IoOperation::Write) => {
+                        waiting.extend((0..self.members.len()).map(|member| Piece {
+                            member,
+                            sector,
+                            operation,
+                            dispatched: false,
+                        }))
+                    }
+                    (RaidLevel::Raid5 { chunk_sectors }, IoOperation::Write)
+                        if self.covers_raid5_row(&request, sector, chunk_sectors) =>
+                    {
+                        // Full row: the parity comes from the new data alone
+                        let (data, parity, member_sector) =
+                            self.locate_raid5(sector, chunk_sectors);
+                        for member in [data, parity] {
+                            let write = Piece {
+                                member,
+                                sector: member_sector,
+                                operation,
+                                dispatched: false,
+                            };
+                            if !waiting.contains(&write) {
+                                waiting.push(write);
+                            }
+                        }
+                    }
+                    (RaidLevel::Raid5 { chunk_sectors }, IoOperation::Write) => {
+                        // Read-modify-write: read old data and parity, then write both
+                        let (data, parity, member_sector) =
+                            self.locate_raid5(sector, chunk_sectors);
+                        for member in [data, parity] {
+                            let read = Piece {
+                                member,
+                                sector: member_sector,
+                                operation: IoOperation::Read,
+                                dispatched: false,
+                            };
+                            let write = Piece {
+                                operation: IoOperation::Write,
+                                ..read
+                            };
+                            if !waiting.contains(&read) {
+                                waiting.push(read);
+                                then_write.push(write);
+                            }
+                        }
+                    }
+                    _ => {
+                        let (member, member_sector) = self.locate(sector);
+                        waiting.push(Piece {
+                            member,
+                            sector: member_sector,
+                            operation,
+                            dispatched: false,
+                        });
+                    }
+                }
+            }
+
+            let merges = waiting
+                .iter()
+                .map(|piece| self.queue_piece(piece, &request))
+                .collect();
+            self.pending.push(PendingOp {
+                request,
+                waiting,
+                then_write,
+                error: None,
+            });
+            Ok(merges)
+        }
+
+        fn queue_piece(&mut self, piece: &Piece, request: &Request) -> Option<MergeKind> {
+            if self.members.len() > 1 {
+                println!(
+                    "[VOLUME] Sector {} -> disk {} sector {} ({:?})",
+                    request.sector, piece.member, piece.sector, piece.operation
+                );
+            }
+            let member_request = Request {
+                sector: piece.sector,
+                count: 1,
+                operation: piece.operation,
+                ..request.clone()
+            };
+            self.members[piece.member]
+                .scheduler
+                .add_request(member_request)
+        }
+
+        /// Ties the queued pieces a member request serves to it, one per sector and
+        /// oldest logical request first. Pieces of other requests for the same sectors
+        /// stay queued until a later dispatch serves them.
+        fn claim_pieces(pending: &mut [PendingOp], member: usize, request: &Request) {
+            for sector in request.sector..request.end() {
+                let piece = pending
+                    .iter_mut()
+                    .flat_map(|op| op.waiting.iter_mut())
+                    .find(|piece| {
+                        !piece.dispatched
+                            && piece.member == member
+                            && piece.operation == request.operation
+                            && piece.sector == sector
+                    });
+                if let Some(piece) = piece {
+                    piece.dispatched = true;
+                }
+            }
+        }
+
+        /// Starts the next queued request on every idle member. Returns the member,
+        /// request and access time of each dispatch. Failed requests complete at `now`
+        /// with their error and are left out.
+        pub fn start_idle_members(&mut self, now: u32) -> Vec<(usize, Request, u32)> {
+            let mut dispatched = Vec::new();
+            for index in 0..self.members.len() {
+                let member = &mut self.members[index];
+                if member.in_flight.is_some() || !member.scheduler.has_requests() {
+                    continue;
+                }
+                let Some(request) = member.scheduler.get_next_request(member.head_sector) else {
+                    continue;
+                };
+                println!(
+                    "[Time {}] SCHEDULER: Disk {} retrieved request for sector {} x{} ({:?})",
+                    now, index, request.sector, request.count, request.operation
+                );
+                Self::claim_pieces(&mut self.pending, index, &request);
+                let spin_up = member.device.power().map_or(0, |power| power.wake(now));
+                let result = member
+                    .device
+                    .access(request.sector, request.count, request.operation);
+                let disk_time = match result {
+                    Ok(disk_time) => {
+                        if let Some(power) = member.device.power() {
+                            power.busy(disk_time);
+                        }
+                        spin_up + disk_time
+                    }
+                    Err(err) => {
+                        // The request completes right away, without its data
+                        println!(
+                            "[ERROR] Disk {} ({}) failed sector {} x{}: {}",
+                            index,
+                            member.device.name(),
+                            request.sector,
+                            request.count,
+                            err
+                        );
+                        member.stats.failed += 1;
+                        member.busy_until = now + spin_up;
+                        member.in_flight = Some(request);
+                        member.error = Some(err);
+                        continue;
+                    }
+                };
+                println!(
+                    "[Time {}] DRIVER: Disk {} ({}) accessing sector {} x{}. Time: {}ms.",
+                    now,
+                    index,
+                    member.device.name(),
+                    request.sector,
+                    request.count,
+                    disk_time
+                );
+                member.scheduler.print_queue_status();
+                member.head_sector = request.end() - 1;
+                member.busy_until = now + disk_time;
+                member.stats.dispatched += 1;
+                member.stats.busy_time += disk_time;
+                match request.operation {
+                    IoOperation::Read => member.stats.sectors_read += request.count,
+                    IoOperation::Write => member.stats.sectors_written += request.count,
+                    IoOperation::ZoneReset => {}
+                }
+                member.in_flight = Some(request.clone());
+                dispatched.push((index, request, disk_time));
+            }
+            dispatched
+        }
+
+        /// Earliest time a member finishes its request in flight.
+        pub fn next_completion(&self) -> Option<u32> {
+            self.members
+                .iter()
+                .filter(|member| member.in_flight.is_some())
+                .map(|member| member.busy_until)
+                .min()
+        }
+
+        /// Finishes the member requests done by `now`. Returns the logical requests
+        /// that completed with them, with the error of a member that failed them.
+        pub fn complete(&mut self, now: u32) -> Vec<(Request, Result<(), DeviceError>)> {
+            let mut finished = Vec::new();
+            for index in 0..self.members.len() {
+                let member = &mut self.members[index];
+                if member.busy_until > now {
+                    continue;
+                }
+                let Some(request) = member.in_flight.take() else {
+                    continue;
+                };
+                let error = member.error.take();
+                println!(
+                    "[Time {}] DRIVER: Disk {} completed sector {} x{} ({:?}).",
+                    member.busy_until, index, request.sector, request.count, request.operation
+                );
+                for op in &mut self.pending {
+                    let waiting = op.waiting.len();
+                    op.waiting
+                        .retain(|piece| piece.member != index || !piece.dispatched);
+                    if op.waiting.len() < waiting && op.error.is_none() {
+                        op.error = error.clone();
+                    }
+                }
+                finished.append(&mut self.advance_pending());
+            }
+            finished
+        }
+
+        /// Starts the write phase of RAID 5 updates whose reads are done and takes the
+        /// completed logical requests out of the pending list. An update whose reads
+        /// failed is not written.
+        fn advance_pending(&mut self) -> Vec<(Request, Result<(), DeviceError>)> {
+            let mut writes = Vec::new();
+            let mut done = Vec::new();
+            let mut index = 0;
+            while index < self.pending.len() {
+                let op = &mut self.pending[index];
+                if !op.waiting.is_empty() {
+                    index += 1;
+                } else if !op.then_write.is_empty() && op.error.is_none() {
+                    println!(
+                        "[VOLUME] Old data and parity of sector {} read, writing new ones.",
+                        op.request.sector
+                    );
+                    op.waiting = std::mem::take(&mut op.then_write);
+                    writes.extend(op.waiting.iter().map(|piece| (*piece, op.request.clone())));
+                    index += 1;
+                } else {
+                    let op = self.pending.remove(index);
+                    done.push((op.request, op.error.map_or(Ok(()), Err)));
+                }
+            }
+            for (piece, request) in writes {
+                self.queue_piece(&piece, &request);
+            }
+            done
+        }
+
+        /// Accounts the power used by the members while idle up to `now`.
+        pub fn idle_until(&mut self, now: u32) {
+            for member in &mut self.members {
+                if let Some(power) = member.device.power() {
+                    power.idle_until(now);
+                }
+            }
+        }
+
+        /// True while any logical request is unfinished.
+        pub fn has_work(&self) -> bool {
+            !self.pending.is_empty()
+                || self
+                    .members
+                    .iter()
+                    .any(|member| member.in_flight.is_some() || member.scheduler.has_requests())
+        }
+
+        pub fn print_report(&self) {
+            println!(
+                "[REPORT] Volume: {} over {} disk(s)",
+                self.level,
+                self.members.len()
+            );
+            for (index, member) in self.members.iter().enumerate() {
+                println!(
+                    "[REPORT] Disk {} ({}, {} sectors): {} dispatches, {} sectors read, {} sectors written, {} failed, {}ms busy",
+                    index,
+                    member.device.name(),
+                    member.device.capacity(),
+                    member.stats.dispatched,
+                    member.stats.sectors_read,
+                    member.stats.sectors_written,
+                    member.stats.failed,
+                    member.stats.busy_time
+                );
+                member.device.print_report();
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::disk::disk::{Disk, PowerModel};
+        use crate::scheduler::scheduler::{FifoScheduler, RequestOrigin};
+        use crate::zoned::zoned::{ZoneError, ZonedDisk};
+
+        fn single(device: Box<dyn BlockDevice>) -> Volume<FifoScheduler> {
+            Volume::new(RaidLevel::Single, vec![device], FifoScheduler::new(8)).unwrap()
+        }
+
+        fn write(sector: u32, process_id: u32) -> Request {
+            Request {
+                origin: RequestOrigin::Process(process_id),
+                ..Request::new(sector, IoOperation::Write)
+            }
+        }
+
+        /// Dispatches at `now` and finishes the member request done first. Returns the
+        /// processes whose requests completed and whether they succeeded.
+        fn serve_next(volume: &mut Volume<FifoScheduler>, now: u32) -> Vec<(u32, bool)> {
+            volume.start_idle_members(now);
+            let now = volume.next_completion().unwrap();
+            volume
+                .complete(now)
+                .into_iter()
+                .map(|(request, result)| match request.origin {
+                    RequestOrigin::Process(process_id) => (process_id, result.is_ok()),
+                    origin => panic!("unexpected {:?} request", origin),
+                })
+                .collect()
+        }
+
+        #[test]
+        fn same_sector_writes_complete_one_by_one() {
+            let mut volume = single(Box::new(Disk::new(5, 100, 2, 4, 1)));
+            volume.submit(write(5, 1)).unwrap();
+            volume.start_idle_members(0);
+            // Queued while the first write is on the disk, it is not served by it
+            volume.submit(write(5, 2)).unwrap();
+            let now = volume.next_completion().unwrap();
+            let done: Vec<_> = volume.complete(now);
+            assert_eq!(done.len(), 1);
+            assert_eq!(done[0].0.origin, RequestOrigin::Process(1));
+            assert!(volume.has_work());
+
+            assert_eq!(serve_next(&mut volume, now), vec![(2, true)]);
+            assert!(!volume.has_work());
+        }
+
+        #[test]
+        fn device_error_reaches_logical_request() {
+            let disk = Disk::new(5, 100, 2, 4, 1);
+            let mut volume = single(Box::new(ZonedDisk::new(disk, 50, 4)));
+            volume.submit(write(60, 1)).unwrap(); // Zone 1 is written from sector 50
+            volume.start_idle_members(0);
+            let done = volume.complete(0);
+            assert_eq!(done.len(), 1);
+            assert_eq!(
+                done[0].1,
+                Err(DeviceError::Zone(ZoneError::UnalignedWrite {
+                    zone: 1,
+                    write_pointer: 50,
+                    sector: 60
+                }))
+            );
+
+            volume.submit(write(50, 2)).unwrap();
+            assert_eq!(serve_next(&mut volume, 0), vec![(2, true)]);
+        }
+
+        fn disks(count: usize) -> Vec<Box<dyn BlockDevice>> {
+            (0..count)
+                .map(|_| Box::new(Disk::new(5, 100, 2, 4, 1)) as Box<dyn BlockDevice>)
+                .collect()
+        }
+
+        fn volume(level: RaidLevel, members: usize) -> Volume<FifoScheduler> {
+            Volume::new(level, disks(members), FifoScheduler::new(8)).unwrap()
+        }
+
+        /// Serves everything queued, returns the sectors each member read and wrote.
+        fn drain(volume: &mut Volume<FifoScheduler>) -> Vec<(u32, u32)> {
+            let mut now = 0;
+            while volume.has_work() {
+                volume.start_idle_members(now);
+                now = volume.next_completion().unwrap();
+                volume.complete(now);
+            }
+            volume
+                .members
+                .iter()
+                .map(|member| (member.stats.sectors_read, member.stats.sectors_written))
+                .collect()
+        }
+
+        #[test]
+        fn raid0_stripes_chunks_over_members() {
+            let volume = volume(RaidLevel::Raid0 { chunk_sectors: 4 }, 2);
+            assert_eq!(volume.capacity(), 1000);
+            assert_eq!(volume.locate(0), (0, 0));
+            assert_eq!(volume.locate(3), (0, 3));
+            assert_eq!(volume.locate(4), (1, 0));
+            assert_eq!(volume.locate(8), (0, 4));
+            assert_eq!(volume.locate(13), (1, 5));
+        }
+
+        #[test]
+        fn raid5_parity_rotates_over_members() {
+            let volume = volume(RaidLevel::Raid5 { chunk_sectors: 2 }, 3);
+            assert_eq!(volume.capacity(), 1000);
+            // (data member, parity member, member sector)
+            assert_eq!(volume.locate_raid5(0, 2), (0, 2, 0));
+            assert_eq!(volume.locate_raid5(2, 2), (1, 2, 0));
+            assert_eq!(volume.locate_raid5(4, 2), (0, 1, 2));
+            assert_eq!(volume.locate_raid5(6, 2), (2, 1, 2));
+            assert_eq!(volume.locate_raid5(9, 2), (1, 0, 5));
+            assert_eq!(volume.locate_raid5(11, 2), (2, 0, 5));
+            let parity: Vec<usize> = (0..4)
+                .map(|stripe| volume.locate_raid5(stripe * 4, 2).1)
+                .collect();
+            assert_eq!(parity, [2, 1, 0, 2]);
+        }
+
+        #[test]
+        fn raid1_reads_alternate_between_mirrors() {
+            let mut volume = volume(RaidLevel::Raid1, 2);
+            assert_eq!(volume.pick_mirror(10), 0);
+            volume.submit(Request::new(10, IoOperation::Read)).unwrap();
+            // Disk 0 has a read queued now
+            assert_eq!(volume.pick_mirror(11), 1);
+            volume.submit(Request::new(11, IoOperation::Read)).unwrap();
+            assert_eq!(drain(&mut volume), [(1, 0), (1, 0)]);
+
+            // Both idle, the closer head wins
+            volume.members[1].head_sector = 300;
+            assert_eq!(volume.pick_mirror(290), 1);
+        }
+
+        #[test]
+        fn raid5_small_write_reads_then_writes_data_and_parity() {
+            let mut volume = volume(RaidLevel::Raid5 { chunk_sectors: 2 }, 3);
+            volume.submit(write(4, 1)).unwrap();
+            assert_eq!(drain(&mut volume), [(1, 1), (1, 1), (0, 0)]);
+        }
+
+        #[test]
+        fn raid5_full_stripe_write_skips_the_reads() {
+            let mut volume = volume(RaidLevel::Raid5 { chunk_sectors: 2 }, 3);
+            let stripe = Request {
+                count: 4,
+                ..write(0, 1)
+            };
+            volume.submit(stripe).unwrap();
+            assert_eq!(drain(&mut volume), [(0, 2), (0, 2), (0, 2)]);
+        }
+
+        #[test]
+        fn spin_up_adds_to_the_next_access() {
+            let power = PowerModel::new(Some(100), 50);
+            let disk = Disk::new(5, 100, 2, 4, 1).with_power_management(power);
+            let mut volume = single(Box::new(disk));
+            let read = |sector| Request::new(sector, IoOperation::Read);
+            // Seek to track 1 (2ms), rotation latency (4ms), one sector (1ms)
+            volume.submit(read(100)).unwrap();
+            assert_eq!(volume.start_idle_members(0)[0].2, 7);
+            volume.complete(7);
+
+            volume.submit(read(100)).unwrap();
+            assert_eq!(volume.start_idle_members(500)[0].2, 50 + 7);
+        }
+
+        #[test]
+        fn striped_volumes_have_no_zones_to_reset() {
+            let zoned = || {
+                let disk = Disk::new(5, 100, 2, 4, 1);
+                Box::new(ZonedDisk::new(disk, 50, 4)) as Box<dyn BlockDevice>
+            };
+            let mut mirror = Volume::new(
+                RaidLevel::Raid1,
+                vec![zoned(), zoned()],
+                FifoScheduler::new(8),
+            )
+            .unwrap();
+            assert_eq!(mirror.reset_zone(120), Ok(100..150));
+            let level = RaidLevel::Raid0 { chunk_sectors: 4 };
+            let mut striped =
+                Volume::new(level, vec![zoned(), zoned()], FifoScheduler::new(8)).unwrap();
+            assert_eq!(striped.reset_zone(120), Err(DeviceError::NoZones));
+        }
+
+        #[test]
+        fn capacity_overflow_is_rejected() {
+            let huge = || Box::new(Disk::new(u32::MAX / 100, 100, 2, 4, 1)) as Box<dyn BlockDevice>;
+            let level = RaidLevel::Raid0 { chunk_sectors: 4 };
+            let result = Volume::new(level, vec![huge(), huge()], FifoScheduler::new(8));
+            assert_eq!(result.err(), Some(VolumeError::TooLarge));
+        }
+
+        #[test]
+        fn requests_past_the_end_are_rejected() {
+            let mut volume = single(Box::new(Disk::new(5, 100, 2, 4, 1)));
+            let past_end = Request {
+                count: 2,
+                ..write(499, 1)
+            };
+            assert_eq!(
+                volume.submit(past_end),
+                Err(DeviceError::OutOfRange {
+                    sector: 499,
+                    count: 2,
+                    capacity: 500
+                })
+            );
+            assert!(!volume.has_work());
+            assert!(volume.submit(write(499, 1)).is_ok());
+        }
+    }
+}
@@ -0,0 +1,408 @@
+pub mod zoned {
+    use crate::device::device::{BlockDevice, DeviceError};
+    use crate::disk::disk::{Disk, PowerModel, TrackBuffer};
+    use crate::scheduler::scheduler::IoOperation;
+    use std::collections::VecDeque;
+    use std::fmt;
+    use std::ops::Range;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ZoneError {
+        OutOfRange {
+            sector: u32,
+            capacity: u32,
+        },
+        UnalignedWrite {
+            zone: usize,
+            write_pointer: u32,
+            sector: u32,
+        },
+        ConventionalZone(usize), // Conventional zones have no write pointer to reset
+    }
+
+    impl fmt::Display for ZoneError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ZoneError::OutOfRange { sector, capacity } => {
+                    write!(
+                        f,
+                        "sector {} is past the end of the device ({} sectors)",
+                        sector, capacity
+                    )
+                }
+                ZoneError::UnalignedWrite {
+                    zone,
+                    write_pointer,
+                    sector,
+                } => write!(
+                    f,
+                    "write to sector {} in zone {} is not at the write pointer {}",
+                    sector, zone, write_pointer
+                ),
+                ZoneError::ConventionalZone(zone) => {
+                    write!(f, "zone {} is conventional and cannot be reset", zone)
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for ZoneError {}
+
+    /// Who keeps writes sequential. A host-managed drive rejects writes off the write
+    /// pointer, a drive-managed one rewrites the whole zone to absorb them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ZoneManagement {
+        HostManaged,
+        DriveManaged,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ZoneCondition {
+        Empty,
+        Open,
+        Closed, // Partly written, but not counted against the open limit
+        Full,
+    }
+
+    #[derive(Debug)]
+    pub struct Zone {
+        pub start: u32,
+        pub write_pointer: u32, // Next sector a sequential write must start at
+        pub condition: ZoneCondition,
+        pub conventional: bool, // Random writes allowed, no write pointer
+    }
+
+    #[derive(Debug, Default)]
+    pub struct ZoneStats {
+        pub rejected_writes: u32,
+        pub zone_resets: u32,
+        pub rewrites: u32, // Zones read and written again by a drive-managed drive
+        pub sectors_rewritten: u32,
+        pub implicit_closes: u32,
+    }
+
+    /// Part of a write that falls into one zone.
+    struct ZonePart {
+        zone: usize,
+        sector: u32,
+        count: u32,
+    }
+
+    /// Shingled magnetic recording disk. The disk is divided into zones of
+    /// `zone_sectors`, the first `conventional_zones` take random writes, the others
+    /// must be written sequentially at their write pointer. At most `max_open_zones`
+    /// sequential zones are open at once, writing to another one implicitly closes
+    /// the least recently written zone.
+    pub struct ZonedDisk {
+        pub disk: Disk,
+        pub zone_sectors: u32,
+        pub max_open_zones: usize,
+        pub management: ZoneManagement,
+        pub zones: Vec<Zone>,
+        pub open_zones: VecDeque<usize>, // Least recently written first
+        pub stats: ZoneStats,
+    }
+
+    impl ZonedDisk {
+        pub fn new(disk: Disk, zone_sectors: u32, max_open_zones: usize) -> Self {
+            let zone_sectors = zone_sectors.max(1);
+            let zones = (0..disk.capacity().div_ceil(zone_sectors))
+                .map(|zone| Zone {
+                    start: zone * zone_sectors,
+                    write_pointer: zone * zone_sectors,
+                    condition: ZoneCondition::Empty,
+                    conventional: false,
+                })
+                .collect();
+            Self {
+                disk,
+                zone_sectors,
+                max_open_zones: max_open_zones.max(1),
+                management: ZoneManagement::HostManaged,
+                zones,
+                open_zones: VecDeque::new(),
+                stats: ZoneStats::default(),
+            }
+        }
+
+        pub fn with_conventional_zones(mut self, count: usize) -> Self {
+            for (index, zone) in self.zones.iter_mut().enumerate() {
+                zone.conventional = index < count;
+            }
+            self
+        }
+
+        pub fn with_management(mut self, management: ZoneManagement) -> Self {
+            self.management = management;
+            self
+        }
+
+        fn zone_end(&self, zone: usize) -> u32 {
+            (self.zones[zone].start + self.zone_sectors).min(self.disk.capacity())
+        }
+
+        fn split(&self, sector: u32, count: u32) -> Vec<ZonePart> {
+            let mut parts = Vec::new();
+            let mut sector = sector;
+            let end = sector + count;
+            while sector < end {
+                let zone = (sector / self.zone_sectors) as usize;
+                let part_end = end.min(self.zone_end(zone));
+                parts.push(ZonePart {
+                    zone,
+                    sector,
+                    count: part_end - sector,
+                });
+                sector = part_end;
+            }
+            parts
+        }
+
+        fn media_time(&mut self, sector: u32, count: u32, operation: IoOperation) -> u32 {
+            let (track, sector_offset) = self.disk.get_track_sector(sector);
+            self.disk
+                .simulate_access(track, sector_offset, count, operation)
+        }
+
+        fn check_range(&self, sector: u32, count: u32) -> Result<(), ZoneError> {
+            let capacity = self.disk.capacity();
+            if sector as u64 + count as u64 > capacity as u64 {
+                return Err(ZoneError::OutOfRange {
+                    sector: sector.saturating_add(count.max(1) - 1),
+                    capacity,
+                });
+            }
+            Ok(())
+        }
+
+        /// Moves the write pointer of a zone back to its start, dropping its data.
+        pub fn reset(&mut self, zone: usize) -> Result<(), ZoneError> {
+            if self.zones[zone].conventional {
+                return Err(ZoneError::ConventionalZone(zone));
+            }
+            println!("[SMR] Resetting zone {}.", zone);
+            let state = &mut self.zones[zone];
+            state.write_pointer = state.start;
+            state.condition = ZoneCondition::Empty;
+            self.open_zones.retain(|&open| open != zone);
+            self.stats.zone_resets += 1;
+            Ok(())
+        }
+
+        /// Host-managed rules, checked for the whole write before any of it lands.
+        fn check_write(&self, parts: &[ZonePart]) -> Result<(), ZoneError> {
+            for part in parts {
+                let zone = &self.zones[part.zone];
+                if !zone.conventional && part.sector != zone.write_pointer {
+                    return Err(ZoneError::UnalignedWrite {
+                        zone: part.zone,
+                        write_pointer: zone.write_pointer,
+                        sector: part.sector,
+                    });
+                }
+            }
+            Ok(())
+        }
+
+        /// Opens a zone for writing. At the limit the least recently written zone is
+        /// closed implicitly, its write pointer stays where it is.
+        fn open_zone(&mut self, zone: usize) {
+            if let Some(pos) = self.open_zones.iter().position(|&open| open == zone) {
+                self.open_zones.remove(pos);
+            } else if self.open_zones.len() >= self.max_open_zones {
+                if let Some(closed) = self.open_zones.pop_front() {
+                    println!("[SMR] Open zone limit reached, closing zone {}.", closed);
+                    self.zones[closed].condition = ZoneCondition::Closed;
+                    self.stats.implicit_closes += 1;
+                }
+            }
+            self.open_zones.push_back(zone);
+            self.zones[zone].condition = ZoneCondition::Open;
+        }
+
+        /// Drive-managed handling of a write off the write pointer: the valid part of
+        /// the zone is read, the zone reset and everything written again up to the
+        /// end of the new data.
+        fn rewrite_zone(&mut self, part: &ZonePart) -> Result<u32, ZoneError> {
+            let (start, write_pointer) = {
+                let zone = &self.zones[part.zone];
+                (zone.start, zone.write_pointer)
+            };
+            let end = write_pointer.max(part.sector + part.count);
+            println!(
+                "[SMR] Write to sector {} is off the write pointer {} of zone {}, rewriting sectors {}..{}.",
+                part.sector, write_pointer, part.zone, start, end
+            );
+            let mut time = 0;
+            if write_pointer > start {
+                time += self.media_time(start, write_pointer - start, IoOperation::Read);
+            }
+            self.reset(part.zone)?;
+            self.stats.rewrites += 1;
+            self.stats.sectors_rewritten += end - start;
+            Ok(time + self.media_time(start, end - start, IoOperation::Write))
+        }
+
+        /// Writes sectors zone by zone and advances the write pointers.
+        pub fn write(&mut self, sector: u32, count: u32) -> Result<u32, ZoneError> {
+            self.check_range(sector, count)?;
+            let parts = self.split(sector, count);
+            if self.management == ZoneManagement::HostManaged {
+                self.check_write(&parts)?;
+            }
+
+            let mut time = 0;
+            for part in &parts {
+                if self.zones[part.zone].conventional {
+                    time += self.media_time(part.sector, part.count, IoOperation::Write);
+                    continue;
+                }
+                self.open_zone(part.zone);
+                time += if part.sector == self.zones[part.zone].write_pointer {
+                    self.media_time(part.sector, part.count, IoOperation::Write)
+                } else {
+                    let rewrite_time = self.rewrite_zone(part)?;
+                    self.open_zone(part.zone);
+                    rewrite_time
+                };
+
+                let end = self.zone_end(part.zone);
+                let zone = &mut self.zones[part.zone];
+                zone.write_pointer = zone.write_pointer.max(part.sector + part.count);
+                if zone.write_pointer == end {
+                    zone.condition = ZoneCondition::Full;
+                    self.open_zones.retain(|&open| open != part.zone);
+                }
+            }
+            Ok(time)
+        }
+    }
+
+    impl BlockDevice for ZonedDisk {
+        fn name(&self) -> &'static str {
+            "SMR"
+        }
+
+        fn capacity(&self) -> u32 {
+            self.disk.capacity()
+        }
+
+        fn access(
+            &mut self,
+            sector: u32,
+            count: u32,
+            operation: IoOperation,
+        ) -> Result<u32, DeviceError> {
+            match operation {
+                IoOperation::Read => {
+                    self.check_range(sector, count)?;
+                    Ok(self.media_time(sector, count, operation))
+                }
+                IoOperation::Write => self.write(sector, count).map_err(|err| {
+                    self.stats.rejected_writes += 1;
+                    DeviceError::from(err)
+                }),
+                IoOperation::ZoneReset => self.reset_zone(sector).map(|_| 0),
+            }
+        }
+
+        fn reset_zone(&mut self, sector: u32) -> Result<Range<u32>, DeviceError> {
+            self.check_range(sector, 1)?;
+            let zone = (sector / self.zone_sectors) as usize;
+            self.reset(zone)?;
+            Ok(self.zones[zone].start..self.zone_end(zone))
+        }
+
+        fn track_buffer(&self) -> Option<&TrackBuffer> {
+            self.disk.track_buffer.as_ref()
+        }
+
+        fn power(&mut self) -> Option<&mut PowerModel> {
+            self.disk.power.as_mut()
+        }
+
+        fn print_report(&self) {
+            let count = |condition: ZoneCondition| {
+                self.zones
+                    .iter()
+                    .filter(|zone| !zone.conventional && zone.condition == condition)
+                    .count()
+            };
+            println!(
+                "[REPORT] SMR ({:?}): {} zones of {} sectors, {} conventional, {} empty, {} open, {} closed, {} full",
+                self.management,
+                self.zones.len(),
+                self.zone_sectors,
+                self.zones.iter().filter(|zone| zone.conventional).count(),
+                count(ZoneCondition::Empty),
+                count(ZoneCondition::Open),
+                count(ZoneCondition::Closed),
+                count(ZoneCondition::Full)
+            );
+            println!(
+                "[REPORT] SMR: {} writes rejected, {} zone resets, {} zone rewrites of {} sectors, {} implicit closes",
+                self.stats.rejected_writes,
+                self.stats.zone_resets,
+                self.stats.rewrites,
+                self.stats.sectors_rewritten,
+                self.stats.implicit_closes
+            );
+            self.disk.print_report();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn host_managed_closes_least_recently_written_zone() {
+            let disk = Disk::new(5, 100, 2, 4, 1);
+            let mut smr = ZonedDisk::new(disk, 50, 2);
+            for zone in 0..3 {
+                assert!(smr.access(zone * 50, 4, IoOperation::Write).is_ok());
+            }
+            assert_eq!(smr.open_zones, [1, 2]);
+            assert_eq!(smr.zones[0].condition, ZoneCondition::Closed);
+            assert_eq!(smr.stats.implicit_closes, 1);
+
+            // A closed zone reopens at its write pointer
+            assert!(smr.access(4, 4, IoOperation::Write).is_ok());
+            assert_eq!(smr.open_zones, [2, 0]);
+            assert_eq!(smr.zones[0].write_pointer, 8);
+            assert_eq!(smr.stats.rejected_writes, 0);
+        }
+
+        #[test]
+        fn reads_past_the_end_are_rejected() {
+            let mut smr = ZonedDisk::new(Disk::new(5, 100, 2, 4, 1), 50, 2);
+            assert!(smr.access(498, 2, IoOperation::Read).is_ok());
+            assert_eq!(
+                smr.access(499, 2, IoOperation::Read),
+                Err(DeviceError::Zone(ZoneError::OutOfRange {
+                    sector: 500,
+                    capacity: 500
+                }))
+            );
+        }
+
+        #[test]
+        fn reset_rewinds_the_zone_holding_the_sector() {
+            let disk = Disk::new(5, 100, 2, 4, 1);
+            let mut smr = ZonedDisk::new(disk, 50, 2).with_conventional_zones(1);
+            assert!(smr.access(50, 4, IoOperation::Write).is_ok());
+            assert_eq!(smr.reset_zone(60), Ok(50..100));
+            assert_eq!(smr.zones[1].write_pointer, 50);
+            assert_eq!(smr.zones[1].condition, ZoneCondition::Empty);
+            assert!(smr.open_zones.is_empty());
+            assert!(smr.access(50, 1, IoOperation::Write).is_ok());
+
+            assert_eq!(
+                smr.access(10, 1, IoOperation::ZoneReset),
+                Err(DeviceError::Zone(ZoneError::ConventionalZone(0)))
+            );
+            let mut disk = Disk::new(5, 100, 2, 4, 1);
+            assert_eq!(disk.reset_zone(60), Err(DeviceError::NoZones));
+        }
+    }
+}